@@ -0,0 +1,180 @@
+//the event bus, lua registers handlers with config:on and they run when the editor does the matching thing
+//events that need an answer before going ahead, like InsertCharPre, run their handlers right away,
+//the rest are queued where they happen and the main loop runs them once nothing is borrowed
+use mlua::{Function, Lua, Table};
+use regex::Regex;
+
+use crate::{pane::Pane, Config, Panes};
+
+//handlers can fire events of their own, this many rounds of that is taken as a loop and the rest is dropped
+const MAX_ROUNDS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+    BufRead,
+    BufWritePre,
+    BufWritePost,
+    ModeChanged,
+    CursorMoved,
+    TextChanged,
+    InsertCharPre,
+    PaneEnter,
+    PaneLeave,
+    WindowResized,
+    FocusGained,
+    FocusLost,
+    VimLeave,
+}
+
+const EVENTS: [(Event, &str); 13] = [
+    (Event::BufRead, "BufRead"),
+    (Event::BufWritePre, "BufWritePre"),
+    (Event::BufWritePost, "BufWritePost"),
+    (Event::ModeChanged, "ModeChanged"),
+    (Event::CursorMoved, "CursorMoved"),
+    (Event::TextChanged, "TextChanged"),
+    (Event::InsertCharPre, "InsertCharPre"),
+    (Event::PaneEnter, "PaneEnter"),
+    (Event::PaneLeave, "PaneLeave"),
+    (Event::WindowResized, "WindowResized"),
+    (Event::FocusGained, "FocusGained"),
+    (Event::FocusLost, "FocusLost"),
+    (Event::VimLeave, "VimLeave"),
+];
+
+impl Event {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        EVENTS.iter().find(|(_, n)| n.eq_ignore_ascii_case(name)).map(|(e, _)| *e).ok_or(format!("unknown event {name:?}"))
+    }
+    pub fn name(&self) -> &'static str {
+        EVENTS.iter().find(|(e, _)| e == self).unwrap().1
+    }
+}
+
+struct Handler {
+    id: usize,
+    event: Event,
+    //matched against the pane's path, or old:new mode chars for ModeChanged, nil matches everything
+    pattern: Option<Regex>,
+    func: Function,
+}
+
+pub struct Autocmds {
+    handlers: Vec<Handler>,
+    next_id: usize,
+    //events that don't belong to a pane, with what patterns are matched against
+    pub queue: Vec<(Event, String)>,
+    //the pane that was current the last time round, for PaneEnter and PaneLeave
+    current: Option<usize>,
+}
+
+impl Autocmds {
+    pub fn new() -> Self {
+        Self { handlers: Vec::new(), next_id: 1, queue: Vec::new(), current: None }
+    }
+    //`events` can name several separated by commas, they all share the returned id
+    pub fn add(&mut self, events: &str, pattern: Option<&str>, func: Function) -> Result<usize, String> {
+        let events = events.split(',').map(|e| Event::from_name(e.trim())).collect::<Result<Vec<_>, _>>()?;
+        let pattern = pattern.map(glob).transpose()?;
+        let id = self.next_id;
+        self.next_id += 1;
+        for event in events {
+            self.handlers.push(Handler { id, event, pattern: pattern.clone(), func: func.clone() });
+        }
+        Ok(id)
+    }
+    pub fn remove(&mut self, id: usize) -> bool {
+        let len = self.handlers.len();
+        self.handlers.retain(|h| h.id != id);
+        self.handlers.len() != len
+    }
+    pub fn handlers(&self, event: Event, matched: &str) -> Vec<Function> {
+        self.handlers.iter()
+            .filter(|h| h.event == event && h.pattern.as_ref().is_none_or(|p| p.is_match(matched)))
+            .map(|h| h.func.clone())
+            .collect()
+    }
+}
+
+//* is any run of chars, slashes too so *.rs matches a full path, and ? is any one char
+fn glob(pattern: &str) -> Result<Regex, String> {
+    let mut re = "^".to_string();
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).map_err(|e| e.to_string())
+}
+
+//calls `handlers` with a table saying what happened, `pane` goes in it as ev.pane and `fill` adds the rest,
+//the table is returned so the handlers of Pre events can answer through it
+pub fn run(lua: &Lua, handlers: Vec<Function>, event: Event, matched: &str, pane: Option<&mut Pane>, fill: impl FnOnce(&Table) -> mlua::Result<()>) -> mlua::Result<Table> {
+    let ev = lua.create_table()?;
+    ev.set("event", event.name())?;
+    ev.set("match", matched)?;
+    fill(&ev)?;
+    lua.scope(|scope| {
+        if let Some(pane) = pane {
+            ev.set("pane", scope.create_userdata_ref_mut(pane)?)?;
+        }
+        for func in handlers {
+            func.call::<()>(&ev)?;
+        }
+        Ok(())
+    })?;
+    //the pane only lived as long as the scope
+    ev.set("pane", mlua::Value::Nil)?;
+    Ok(ev)
+}
+
+//runs everything queued since the last time round, returns whether any handler ran
+pub fn dispatch(lua: &Lua, config: &mut Config, panes: &mut Panes) -> bool {
+    let mut ran = false;
+    for _ in 0..MAX_ROUNDS {
+        let mut fired: Vec<(Event, String, Option<usize>)> = config.autocmds.queue.drain(..).map(|(e, m)| (e, m, None)).collect();
+        let current = panes.current_pane;
+        if config.autocmds.current != Some(current) {
+            if let Some(old) = config.autocmds.current.filter(|&old| old < panes.panes.len()) {
+                fired.push((Event::PaneLeave, panes.panes[old].event_match(), Some(old)));
+            }
+            fired.push((Event::PaneEnter, panes.panes[current].event_match(), Some(current)));
+            config.autocmds.current = Some(current);
+        }
+        for (i, pane) in panes.panes.iter_mut().enumerate() {
+            if pane.take_text_changed() {
+                pane.fired.push(Event::TextChanged);
+            }
+            if pane.take_cursor_moved() {
+                pane.fired.push(Event::CursorMoved);
+            }
+            let matched = pane.event_match();
+            for event in std::mem::take(&mut pane.fired) {
+                //a cursor moved five times since the last frame only needs telling once
+                if !fired.iter().any(|(e, _, p)| *e == event && *p == Some(i)) {
+                    fired.push((event, matched.clone(), Some(i)));
+                }
+            }
+        }
+        if fired.is_empty() {
+            break;
+        }
+        for (event, matched, i) in fired {
+            let handlers = config.autocmds.handlers(event, &matched);
+            if handlers.is_empty() {
+                continue;
+            }
+            ran = true;
+            //a handler before this one may have closed the pane
+            let pane = i.and_then(|i| panes.panes.get_mut(i));
+            let index = i.map(|i| i + 1);
+            if let Err(e) = run(lua, handlers, event, &matched, pane, |ev| ev.set("index", index)) {
+                config.cmdline.error(e);
+            }
+        }
+    }
+    ran
+}
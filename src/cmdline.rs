@@ -0,0 +1,524 @@
+//the command line at the bottom of the window, and the : commands lua registers
+use std::{collections::HashMap, iter::Peekable, path::PathBuf, str::Chars};
+
+use mlua::{Function, Lua, Table, Value};
+use sdl2::{keyboard::{Keycode, Mod}, rect::{Point, Rect}, ttf::FontStyle};
+
+use crate::{font::Fonts, pane::Pane, Config};
+
+type Canvas = sdl2::render::Canvas<sdl2::video::Window>;
+
+const MAX_MESSAGES: usize = 200;
+
+pub enum Complete {
+    File,
+    Command,
+    Function(Function),
+}
+
+pub struct Command {
+    pub func: Function,
+    //how many arguments it takes, one of 0 1 ? * +
+    pub nargs: char,
+    pub range: bool,
+    pub bang: bool,
+    pub complete: Option<Complete>,
+}
+
+impl Command {
+    pub fn new(func: Function, opts: Option<Table>) -> mlua::Result<Self> {
+        let Some(opts) = opts else {
+            return Ok(Self {
+                func,
+                nargs: '0',
+                range: false,
+                bang: false,
+                complete: None,
+            });
+        };
+        let nargs = match opts.get::<Value>("nargs")? {
+            Value::Nil => '0',
+            Value::Integer(0) => '0',
+            Value::Integer(1) => '1',
+            Value::String(s) if matches!(s.to_str()?.as_ref(), "0" | "1" | "?" | "*" | "+") => {
+                s.to_str()?.chars().next().unwrap()
+            }
+            other => return Err(mlua::Error::runtime(format!("invalid nargs {other:?}, expected 0, 1, ?, * or +"))),
+        };
+        let complete = match opts.get::<Value>("complete")? {
+            Value::Nil => None,
+            Value::Function(f) => Some(Complete::Function(f)),
+            Value::String(s) if s == "file" => Some(Complete::File),
+            Value::String(s) if s == "command" => Some(Complete::Command),
+            other => return Err(mlua::Error::runtime(format!("invalid complete {other:?}, expected \"file\", \"command\" or a function"))),
+        };
+        Ok(Self {
+            func,
+            nargs,
+            range: opts.get::<Option<bool>>("range")?.unwrap_or(false),
+            bang: opts.get::<Option<bool>>("bang")?.unwrap_or(false),
+            complete,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Base {
+    Line(usize),
+    Current,
+    Last,
+    Mark(char),
+}
+
+//a line in a range like `.+2` or `'<`
+#[derive(Debug, Clone, Copy)]
+pub struct Address {
+    pub base: Base,
+    pub offset: i64,
+}
+
+#[derive(Debug)]
+pub struct Parsed {
+    pub range: Vec<Address>,
+    pub name: String,
+    pub bang: bool,
+    pub args: String,
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut n = String::new();
+    while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+        n.push(*c);
+        chars.next();
+    }
+    n.parse().ok()
+}
+
+fn parse_address(chars: &mut Peekable<Chars>) -> Result<Option<Address>, String> {
+    let base = match chars.peek() {
+        Some(c) if c.is_ascii_digit() => Base::Line(parse_number(chars).ok_or("invalid range")?),
+        Some('.') => {
+            chars.next();
+            Base::Current
+        }
+        Some('$') => {
+            chars.next();
+            Base::Last
+        }
+        Some('\'') => {
+            chars.next();
+            Base::Mark(chars.next().ok_or("missing mark name")?)
+        }
+        Some('+') | Some('-') => Base::Current,
+        _ => return Ok(None),
+    };
+    let mut offset: i64 = 0;
+    while let Some(&c) = chars.peek().filter(|c| **c == '+' || **c == '-') {
+        chars.next();
+        //a bare + or - is one line, digits that don't fit are an error rather than a guess
+        let n = match chars.peek() {
+            Some(d) if d.is_ascii_digit() => parse_number(chars).and_then(|n| i64::try_from(n).ok()).ok_or("invalid range")?,
+            _ => 1,
+        };
+        offset = if c == '+' { offset.checked_add(n) } else { offset.checked_sub(n) }.ok_or("invalid range")?;
+    }
+    Ok(Some(Address { base, offset }))
+}
+
+//splits `[range]name[!] args` into its parts
+pub fn parse(line: &str) -> Result<Parsed, String> {
+    let mut chars = line.trim_start_matches([' ', ':']).chars().peekable();
+    let mut range = Vec::new();
+    if chars.peek() == Some(&'%') {
+        chars.next();
+        range.push(Address { base: Base::Line(1), offset: 0 });
+        range.push(Address { base: Base::Last, offset: 0 });
+    } else if let Some(addr) = parse_address(&mut chars)? {
+        range.push(addr);
+        if chars.peek() == Some(&',') || chars.peek() == Some(&';') {
+            chars.next();
+            range.push(parse_address(&mut chars)?.ok_or("missing address after ,")?);
+        }
+    }
+    while chars.peek() == Some(&' ') {
+        chars.next();
+    }
+    let mut name = String::new();
+    while let Some(c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+        name.push(*c);
+        chars.next();
+    }
+    let bang = chars.peek() == Some(&'!');
+    if bang {
+        chars.next();
+    }
+    let args = chars.collect::<String>().trim().to_string();
+    Ok(Parsed { range, name, bang, args })
+}
+
+impl Parsed {
+    //turns the range into 1 based line numbers using the pane's cursor and marks
+    pub fn resolve(&self, pane: &Pane) -> Result<(usize, usize), String> {
+        let (_, y) = pane.get_cursor();
+        let current = y as usize + 1;
+        let last = pane.line_count();
+        let line = |addr: &Address| -> Result<usize, String> {
+            let base = match addr.base {
+                Base::Line(n) => n,
+                Base::Current => current,
+                Base::Last => last,
+                Base::Mark(m) => pane.mark(m).ok_or(format!("mark '{m} not set"))?.1 as usize + 1,
+            };
+            let line = i64::try_from(base).ok().and_then(|b| b.checked_add(addr.offset)).ok_or("invalid range")?;
+            if line < 0 || line as usize > last {
+                return Err("invalid range".to_string());
+            }
+            Ok(line.max(1) as usize)
+        };
+        match self.range.as_slice() {
+            [] => Ok((current, current)),
+            [a] => {
+                let l = line(a)?;
+                Ok((l, l))
+            }
+            [a, b, ..] => {
+                let (a, b) = (line(a)?, line(b)?);
+                Ok((a.min(b), a.max(b)))
+            }
+        }
+    }
+}
+
+//looks up a command by its name or by a prefix only one command has
+pub fn find<'a>(commands: &'a HashMap<String, Command>, name: &str) -> Result<&'a Command, String> {
+    if let Some(cmd) = commands.get(name) {
+        return Ok(cmd);
+    }
+    let matches: Vec<&String> = commands.keys().filter(|k| k.starts_with(name)).collect();
+    match matches.as_slice() {
+        [one] => Ok(&commands[*one]),
+        [] => Err(format!("not an editor command: {name}")),
+        _ => Err(format!("ambiguous command: {name}")),
+    }
+}
+
+//checks the parsed line against what the command accepts and builds the table it gets called with
+pub fn command_args(lua: &Lua, cmd: &Command, parsed: &Parsed, (line1, line2): (usize, usize)) -> mlua::Result<Table> {
+    let fargs: Vec<&str> = parsed.args.split_whitespace().collect();
+    let ok = match cmd.nargs {
+        '0' => fargs.is_empty(),
+        '1' => !parsed.args.is_empty(),
+        '?' => fargs.len() <= 1,
+        '+' => !fargs.is_empty(),
+        _ => true,
+    };
+    if !ok {
+        return Err(mlua::Error::runtime(match cmd.nargs {
+            '0' => "trailing characters",
+            _ => "wrong number of arguments",
+        }));
+    }
+    if parsed.bang && !cmd.bang {
+        return Err(mlua::Error::runtime("no ! allowed"));
+    }
+    if !parsed.range.is_empty() && !cmd.range {
+        return Err(mlua::Error::runtime("no range allowed"));
+    }
+    let t = lua.create_table()?;
+    t.set("name", parsed.name.clone())?;
+    t.set("args", parsed.args.clone())?;
+    t.set("fargs", fargs)?;
+    t.set("bang", parsed.bang)?;
+    t.set("range", parsed.range.len().min(2))?;
+    t.set("line1", line1)?;
+    t.set("line2", line2)?;
+    Ok(t)
+}
+
+pub enum CmdLineEvent {
+    Edited,
+    Cancel,
+    Submit(String),
+    Unhandled,
+}
+
+struct Completion {
+    matches: Vec<String>,
+    index: usize,
+    //char index the completed word starts at
+    start: usize,
+}
+
+pub struct CmdLine {
+    pub prompt: char,
+    text: Vec<char>,
+    cursor: usize,
+    history: HashMap<char, Vec<String>>,
+    history_pos: Option<usize>,
+    completion: Option<Completion>,
+    //shown in place of the command line when it isn't open
+    pub message: Option<String>,
+    //every error shown so far with its traceback, oldest first, for :messages
+    pub messages: Vec<String>,
+}
+
+impl CmdLine {
+    pub fn new() -> Self {
+        Self {
+            prompt: ':',
+            text: Vec::new(),
+            cursor: 0,
+            history: HashMap::new(),
+            history_pos: None,
+            completion: None,
+            message: None,
+            messages: Vec::new(),
+        }
+    }
+    //shows the first line of an error and keeps all of it for :messages
+    pub fn error(&mut self, e: impl ToString) {
+        let text = e.to_string();
+        if self.messages.len() >= MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+        self.messages.push(text.clone());
+        self.message = Some(text);
+    }
+    pub fn open(&mut self, prompt: char, text: &str) {
+        self.prompt = prompt;
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+        self.history_pos = None;
+        self.completion = None;
+        self.message = None;
+    }
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+    fn set_text(&mut self, text: &str) {
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+    }
+    fn history_step(&mut self, back: bool) {
+        let history = self.history.entry(self.prompt).or_default();
+        if history.is_empty() {
+            return;
+        }
+        let pos = match (self.history_pos, back) {
+            (None, true) => history.len() - 1,
+            (None, false) => return,
+            (Some(p), true) => p.saturating_sub(1),
+            (Some(p), false) if p + 1 >= history.len() => {
+                self.history_pos = None;
+                self.set_text("");
+                return;
+            }
+            (Some(p), false) => p + 1,
+        };
+        let text = history[pos].clone();
+        self.history_pos = Some(pos);
+        self.set_text(&text);
+    }
+    fn complete(&mut self, back: bool, commands: &HashMap<String, Command>) {
+        if self.completion.is_none() {
+            let before: String = self.text[..self.cursor].iter().collect();
+            let start = before.rfind(' ').map(|i| before[..i].chars().count() + 1).unwrap_or(0);
+            let word: String = self.text[start..self.cursor].iter().collect();
+            let parsed = parse(&before);
+            let mut matches = match parsed {
+                //still typing the command name
+                Ok(p) if !before.contains(' ') && p.args.is_empty() && !p.bang => {
+                    let prefix = p.name;
+                    let mut names: Vec<String> = commands.keys().filter(|k| k.starts_with(&prefix)).cloned().collect();
+                    names.sort();
+                    let lead: String = before.chars().take(before.chars().count() - prefix.chars().count()).collect();
+                    names.into_iter().map(|n| lead.clone() + &n).collect()
+                }
+                Ok(p) => match find(commands, &p.name).ok().and_then(|c| c.complete.as_ref()) {
+                    Some(Complete::File) => complete_file(&word),
+                    Some(Complete::Command) => {
+                        let mut names: Vec<String> = commands.keys().filter(|k| k.starts_with(&word)).cloned().collect();
+                        names.sort();
+                        names
+                    }
+                    Some(Complete::Function(f)) => match f.call::<Vec<String>>((word.clone(), before.clone())) {
+                        Ok(m) => m,
+                        Err(e) => {
+                            self.error(e);
+                            Vec::new()
+                        }
+                    },
+                    None => Vec::new(),
+                },
+                Err(_) => Vec::new(),
+            };
+            matches.dedup();
+            if matches.is_empty() {
+                return;
+            }
+            self.completion = Some(Completion { matches, index: usize::MAX, start });
+        }
+        let c = self.completion.as_mut().unwrap();
+        let n = c.matches.len();
+        c.index = match (c.index, back) {
+            (usize::MAX, false) => 0,
+            (usize::MAX, true) => n - 1,
+            (i, false) => (i + 1) % n,
+            (i, true) => (i + n - 1) % n,
+        };
+        let word: Vec<char> = c.matches[c.index].chars().collect();
+        let start = c.start;
+        self.text.splice(start..self.cursor, word.iter().cloned());
+        self.cursor = start + word.len();
+    }
+    pub fn handle(&mut self, key: Keycode, keymod: Mod, text: Option<String>, commands: &HashMap<String, Command>) -> CmdLineEvent {
+        let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+        if key != Keycode::Tab {
+            self.completion = None;
+        }
+        match key {
+            Keycode::Escape => return CmdLineEvent::Cancel,
+            Keycode::Return => {
+                let line = self.text();
+                if !line.trim().is_empty() {
+                    let history = self.history.entry(self.prompt).or_default();
+                    history.retain(|h| *h != line);
+                    history.push(line.clone());
+                }
+                return CmdLineEvent::Submit(line);
+            }
+            Keycode::Backspace => {
+                if self.text.is_empty() {
+                    return CmdLineEvent::Cancel;
+                }
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.text.remove(self.cursor);
+                }
+            }
+            Keycode::Delete => {
+                if self.cursor < self.text.len() {
+                    self.text.remove(self.cursor);
+                }
+            }
+            Keycode::Left => self.cursor = self.cursor.saturating_sub(1),
+            Keycode::Right => self.cursor = (self.cursor + 1).min(self.text.len()),
+            Keycode::Home => self.cursor = 0,
+            Keycode::End => self.cursor = self.text.len(),
+            Keycode::Up => self.history_step(true),
+            Keycode::Down => self.history_step(false),
+            //only commands complete, not search patterns
+            Keycode::Tab if self.prompt == ':' => self.complete(shift, commands),
+            Keycode::Space => {
+                self.text.insert(self.cursor, ' ');
+                self.cursor += 1;
+            }
+            _ => {
+                let Some(text) = text else {
+                    return CmdLineEvent::Unhandled;
+                };
+                for c in text.chars() {
+                    self.text.insert(self.cursor, c);
+                    self.cursor += 1;
+                }
+            }
+        }
+        CmdLineEvent::Edited
+    }
+    pub fn render(&self, canvas: &mut Canvas, fonts: &mut Fonts, config: &Config, rect: Rect, active: bool) {
+        canvas.set_clip_rect(rect);
+        canvas.set_draw_color(config.theme.bg("Normal"));
+        canvas.fill_rect(rect).unwrap();
+        let font = (fonts.find_font(&[&config.monospace]), config.font_size);
+        let line: Vec<char> = if active {
+            std::iter::once(self.prompt).chain(self.text.iter().cloned()).collect()
+        } else if let Some(message) = &self.message {
+            message.lines().next().unwrap_or("").chars().collect()
+        } else {
+            return;
+        };
+        let mut x = rect.x;
+        for (i, c) in line.iter().enumerate() {
+            if active && i == self.cursor + 1 {
+                canvas.set_draw_color(config.theme.fg("Normal"));
+                canvas.fill_rect(Rect::new(x, rect.y, 2, rect.height())).unwrap();
+            }
+            fonts.draw_char(canvas, &font, FontStyle::NORMAL, *c, config.theme.fg("Normal"), Point::new(x, rect.y));
+            x += fonts.char_size(&font, FontStyle::NORMAL, *c).0 as i32;
+        }
+        if active && self.cursor + 1 == line.len() {
+            canvas.set_draw_color(config.theme.fg("Normal"));
+            canvas.fill_rect(Rect::new(x, rect.y, 2, rect.height())).unwrap();
+        }
+    }
+}
+
+fn complete_file(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let path = if dir.is_empty() { PathBuf::from(".") } else { PathBuf::from(dir) };
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return Vec::new();
+    };
+    let mut out: Vec<String> = entries.flatten().filter_map(|e| {
+        let name = e.file_name().to_string_lossy().to_string();
+        if !name.starts_with(prefix) || (prefix.is_empty() && name.starts_with('.')) {
+            return None;
+        }
+        let slash = if e.path().is_dir() { "/" } else { "" };
+        Some(format!("{dir}{name}{slash}"))
+    }).collect();
+    out.sort();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::Rect;
+
+    use super::parse;
+    use crate::pane::{BufType, Pane};
+
+    //ten lines with the cursor on line 5 and a selection over lines 3 to 7
+    fn pane() -> Pane {
+        let mut pane = Pane::text(Rect::new(0, 0, 1, 1), 0, None);
+        let lines: Vec<String> = (1..=10).map(|n| format!("line {n}")).collect();
+        pane.set_lines(0, 1, &lines);
+        pane.set_cursor(0, 4);
+        let BufType::Text{marks, ..} = &mut pane.buf;
+        marks.insert('<', (0, 2));
+        marks.insert('>', (0, 6));
+        pane
+    }
+
+    fn resolve(line: &str) -> Result<(usize, usize), String> {
+        parse(line)?.resolve(&pane())
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(resolve("%s/a/b/"), Ok((1, 10)));
+        assert_eq!(resolve("."), Ok((5, 5)));
+        assert_eq!(resolve(".+3"), Ok((8, 8)));
+        assert_eq!(resolve("'<,'>d"), Ok((3, 7)));
+        assert_eq!(resolve("$-1"), Ok((9, 9)));
+        assert_eq!(resolve("4,2"), Ok((2, 4)));
+        assert_eq!(resolve("d"), Ok((5, 5)));
+    }
+
+    #[test]
+    fn line_zero_is_the_first_line() {
+        assert_eq!(resolve("0"), Ok((1, 1)));
+        assert_eq!(resolve("0,3"), Ok((1, 3)));
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert_eq!(resolve(".+99999999999999999999"), Err("invalid range".to_string()));
+        assert_eq!(resolve("$+1"), Err("invalid range".to_string()));
+        assert_eq!(resolve(".-6"), Err("invalid range".to_string()));
+        assert_eq!(resolve("'a"), Err("mark 'a not set".to_string()));
+    }
+}
@@ -3,7 +3,7 @@ local function move(dx, dy)
         local x, y = pane:get_cursor()
         x = x + dx
         y = y + dy
-        if x >= 0 and y >= 0 then
+        if x >= 0 and y >= 0 and y < pane:line_count() then
             pane:set_cursor(x, y)
         end
     end)
@@ -26,15 +26,62 @@ config:key("i", "<Esc>", function()
     config.mode = "n"
 end)
 
-config:key("n", "v", function()
-    config.mode = "v"
+-- starts a selection of `kind` at the cursor, or changes the kind of the current one
+local function visual(kind)
+    return function()
+        panes:get(0, function(pane)
+            local sel = pane:get_selection()
+            if sel == nil then
+                local x, y = pane:get_cursor()
+                pane:set_selection({x1 = x, y1 = y, x2 = x, y2 = y, kind = kind})
+                config.mode = "v"
+            elseif sel.kind == kind then
+                config.mode = "n"
+            else
+                sel.kind = kind
+                pane:set_selection(sel)
+            end
+        end)
+    end
+end
+
+config:key("nv", "v", visual("char"))
+config:key("nv", "V", visual("line"))
+config:key("nv", "<C-v>", visual("block"))
+
+-- jump to the other end of the selection
+config:key("v", "o", function()
+    panes:get(0, function(pane)
+        local sel = pane:get_selection()
+        pane:set_selection({x1 = sel.x2, y1 = sel.y2, x2 = sel.x1, y2 = sel.y1, kind = sel.kind})
+    end)
 end)
 
-config:key("nv", "$", function()
+config:key("v", "x", function()
     panes:get(0, function(pane)
-        local _, y = pane:get_cursor();
-        pane:set_cursor(pane:linelen(y), y)
+        pane:delete(pane:get_selection())
     end)
+    config.mode = "n"
+end)
+
+config:operator("d", function(pane, region)
+    pane:delete(region)
+end)
+
+config:operator("c", function(pane, region)
+    pane:change(region)
+    config.mode = "i"
+end)
+
+config:operator("y", function(pane, region)
+    pane:yank(region)
+    local x = math.min(region.x1, region.x2)
+    if region.kind == "char" then
+        x = (region.y1 < region.y2 or (region.y1 == region.y2 and region.x1 < region.x2)) and region.x1 or region.x2
+    elseif region.kind == "line" then
+        x = 0
+    end
+    pane:set_cursor(x, math.min(region.y1, region.y2))
 end)
 
 
@@ -44,32 +91,841 @@ function mvc(dx, dy)
     end
 end
 
-config:key("nv", "h", mvc(-1, 0));
-config:key("nv", "j", mvc(0, 1));
-config:key("nv", "k",  mvc(0, -1));
-config:key("nv", "l", mvc(1, 0));
-
 config:key("vin", "<Left>", mvc(-1, 0));
 config:key("vin", "<Down>", mvc(0, 1));
 config:key("vin", "<Up>",  mvc(0, -1));
 config:key("vin", "<Right>", mvc(1, 0));
 
-config:key("vn", "0", function()
+-- motions get the pane and the count, which is nil when none was typed
+-- they return where the cursor goes, or a region for text objects
+
+-- the chars of line y, indexed by column + 1
+local function chars(pane, y)
+    local out = {}
+    for _, c in utf8.codes(pane:get_lines(y, y + 1)[1]) do
+        out[#out + 1] = utf8.char(c)
+    end
+    return out
+end
+
+-- reads the char at x, y, the end of a line reads as nil
+local function reader(pane)
+    return function(x, y)
+        return pane:char_at(x, y)
+    end
+end
+
+-- walks the buffer a char at a time, the end of each line is a step of its own
+local function step(pane, x, y, dir)
+    if dir > 0 then
+        if x < pane:linelen(y) then
+            return x + 1, y
+        elseif y + 1 < pane:line_count() then
+            return 0, y + 1
+        end
+    elseif x > 0 then
+        return x - 1, y
+    elseif y > 0 then
+        return pane:linelen(y - 1), y - 1
+    end
+    return nil
+end
+
+-- 0 for blanks and line ends, 1 for punctuation and 2 for word chars, big words are anything but blanks
+local function classifier(big)
+    return function(c)
+        if c == nil or c:match("^%s$") then
+            return 0
+        elseif big or c:match("^[%w_]$") or #c > 1 then
+            return 2
+        end
+        return 1
+    end
+end
+
+config:motion("h", function(pane, count)
+    local x, y = pane:get_cursor()
+    return math.max(x - (count or 1), 0), y
+end)
+
+config:motion("l", function(pane, count)
+    local x, y = pane:get_cursor()
+    return x + (count or 1), y
+end)
+
+config:motion("j", function(pane, count)
+    local x, y = pane:get_cursor()
+    return x, math.min(y + (count or 1), pane:line_count() - 1)
+end, {linewise = true})
+
+config:motion("k", function(pane, count)
+    local x, y = pane:get_cursor()
+    return x, math.max(y - (count or 1), 0)
+end, {linewise = true})
+
+-- moves by screen rows, which are the same as lines unless the pane wraps
+local function display_line(dir)
+    return function(pane, count)
+        local x, y = pane:get_cursor()
+        local row, col = pane:screen_pos(x, y)
+        for _ = 1, count or 1 do
+            if dir < 0 and row == 0 then
+                if y == 0 then
+                    break
+                end
+                y = y - 1
+                row = pane:screen_rows(y) - 1
+            elseif dir > 0 and row == pane:screen_rows(y) - 1 then
+                if y == pane:line_count() - 1 then
+                    break
+                end
+                y = y + 1
+                row = 0
+            else
+                row = row + dir
+            end
+        end
+        return pane:screen_char(y, row, col), y
+    end
+end
+
+config:motion("gj", display_line(1))
+config:motion("gk", display_line(-1))
+
+config:motion("0", function(pane)
+    local _, y = pane:get_cursor()
+    return 0, y
+end)
+
+config:motion("$", function(pane, count)
+    local _, y = pane:get_cursor()
+    y = math.min(y + (count or 1) - 1, pane:line_count() - 1)
+    return math.max(pane:linelen(y) - 1, 0), y
+end, {inclusive = true})
+
+config:motion("gg", function(pane, count)
+    return 0, math.min((count or 1) - 1, pane:line_count() - 1)
+end, {linewise = true})
+
+config:motion("G", function(pane, count)
+    return 0, math.min(count and count - 1 or pane:line_count(), pane:line_count() - 1)
+end, {linewise = true})
+
+-- an empty line counts as a word, like in vim
+local function word_start(big)
+    local class = classifier(big)
+    return function(pane, count)
+        local at = reader(pane)
+        local x, y = pane:get_cursor()
+        for _ = 1, count or 1 do
+            local k = class(at(x, y))
+            local start = y
+            while k ~= 0 and class(at(x, y)) == k do
+                local nx, ny = step(pane, x, y, 1)
+                if nx == nil then
+                    return x + 1, y
+                end
+                x, y = nx, ny
+            end
+            while class(at(x, y)) == 0 and not (y ~= start and pane:linelen(y) == 0) do
+                local nx, ny = step(pane, x, y, 1)
+                if nx == nil then
+                    return x + 1, y
+                end
+                x, y = nx, ny
+            end
+        end
+        return x, y
+    end
+end
+
+local function word_end(big)
+    local class = classifier(big)
+    return function(pane, count)
+        local at = reader(pane)
+        local x, y = pane:get_cursor()
+        for _ = 1, count or 1 do
+            local nx, ny = step(pane, x, y, 1)
+            while nx ~= nil and class(at(nx, ny)) == 0 do
+                nx, ny = step(pane, nx, ny, 1)
+            end
+            if nx == nil then
+                return x, y
+            end
+            x, y = nx, ny
+            local k = class(at(x, y))
+            nx, ny = step(pane, x, y, 1)
+            while nx ~= nil and class(at(nx, ny)) == k do
+                x, y = nx, ny
+                nx, ny = step(pane, x, y, 1)
+            end
+        end
+        return x, y
+    end
+end
+
+local function word_back(big)
+    local class = classifier(big)
+    return function(pane, count)
+        local at = reader(pane)
+        local x, y = pane:get_cursor()
+        for _ = 1, count or 1 do
+            local nx, ny = step(pane, x, y, -1)
+            while nx ~= nil and class(at(nx, ny)) == 0 and pane:linelen(ny) ~= 0 do
+                nx, ny = step(pane, nx, ny, -1)
+            end
+            if nx == nil then
+                return x, y
+            end
+            x, y = nx, ny
+            local k = class(at(x, y))
+            nx, ny = step(pane, x, y, -1)
+            while k ~= 0 and nx ~= nil and class(at(nx, ny)) == k do
+                x, y = nx, ny
+                nx, ny = step(pane, x, y, -1)
+            end
+        end
+        return x, y
+    end
+end
+
+config:motion("w", word_start(false))
+config:motion("W", word_start(true))
+config:motion("e", word_end(false), {inclusive = true})
+config:motion("E", word_end(true), {inclusive = true})
+config:motion("b", word_back(false))
+config:motion("B", word_back(true))
+
+-- paragraphs end at empty lines
+config:motion("}", function(pane, count)
+    local _, y = pane:get_cursor()
+    local last = pane:line_count() - 1
+    for _ = 1, count or 1 do
+        while y < last and pane:linelen(y) == 0 do
+            y = y + 1
+        end
+        while y < last and pane:linelen(y) ~= 0 do
+            y = y + 1
+        end
+    end
+    y = math.min(y, last)
+    return pane:linelen(y), y
+end)
+
+config:motion("{", function(pane, count)
+    local _, y = pane:get_cursor()
+    for _ = 1, count or 1 do
+        while y > 0 and pane:linelen(y) == 0 do
+            y = y - 1
+        end
+        while y > 0 and pane:linelen(y) ~= 0 do
+            y = y - 1
+        end
+    end
+    return 0, math.max(y, 0)
+end)
+
+-- finds the count'th c on the cursor line, till stops next to it
+local function find(dir, till)
+    return function(pane, count, c)
+        local x, y = pane:get_cursor()
+        local line = chars(pane, y)
+        local i = x
+        for _ = 1, count or 1 do
+            i = i + dir
+            while line[i + 1] ~= nil and line[i + 1] ~= c do
+                i = i + dir
+            end
+            if line[i + 1] == nil then
+                return nil
+            end
+        end
+        if till then
+            i = i - dir
+        end
+        return i, y
+    end
+end
+
+config:motion("f", find(1, false), {char = true, inclusive = true})
+config:motion("t", find(1, true), {char = true, inclusive = true})
+config:motion("F", find(-1, false), {char = true})
+config:motion("T", find(-1, true), {char = true})
+
+-- iw is the word or run of blanks under the cursor, aw takes the blanks after it too
+local function word_object(around, big)
+    local class = classifier(big)
+    return function(pane)
+        local x, y = pane:get_cursor()
+        local line = chars(pane, y)
+        if #line == 0 then
+            return nil
+        end
+        x = math.min(x, #line - 1)
+        local k = class(line[x + 1])
+        local s, e = x, x
+        while s > 0 and class(line[s]) == k do
+            s = s - 1
+        end
+        while e < #line - 1 and class(line[e + 2]) == k do
+            e = e + 1
+        end
+        if around then
+            local after = e
+            while after < #line - 1 and class(line[after + 2]) == 0 do
+                after = after + 1
+            end
+            if after > e then
+                e = after
+            else
+                while s > 0 and class(line[s]) == 0 do
+                    s = s - 1
+                end
+            end
+        end
+        return {x1 = s, y1 = y, x2 = e, y2 = y, kind = "char"}
+    end
+end
+
+config:motion("iw", word_object(false, false), {object = true})
+config:motion("aw", word_object(true, false), {object = true})
+config:motion("iW", word_object(false, true), {object = true})
+config:motion("aW", word_object(true, true), {object = true})
+
+-- the innermost open and close around the cursor, i( is what's between them and a( includes them
+local function pair_object(open, close, around)
+    return function(pane)
+        local at = reader(pane)
+        local cx, cy = pane:get_cursor()
+        local ox, oy = cx, cy
+        local depth = 0
+        while true do
+            local c = at(ox, oy)
+            if c == open then
+                if depth == 0 then
+                    break
+                end
+                depth = depth - 1
+            elseif c == close and not (ox == cx and oy == cy) then
+                depth = depth + 1
+            end
+            ox, oy = step(pane, ox, oy, -1)
+            if ox == nil then
+                return nil
+            end
+        end
+        local ex, ey = step(pane, ox, oy, 1)
+        depth = 0
+        while ex ~= nil do
+            local c = at(ex, ey)
+            if c == close then
+                if depth == 0 then
+                    break
+                end
+                depth = depth - 1
+            elseif c == open then
+                depth = depth + 1
+            end
+            ex, ey = step(pane, ex, ey, 1)
+        end
+        if ex == nil then
+            return nil
+        end
+        if around then
+            return {x1 = ox, y1 = oy, x2 = ex, y2 = ey, kind = "char"}
+        end
+        local sx, sy = step(pane, ox, oy, 1)
+        if sx == ex and sy == ey then
+            return nil
+        end
+        ex, ey = step(pane, ex, ey, -1)
+        -- line breaks right inside the pair are left alone
+        if sx == pane:linelen(sy) and sy < ey then
+            sx, sy = 0, sy + 1
+        end
+        if ex == pane:linelen(ey) and ex > 0 then
+            ex = ex - 1
+        end
+        return {x1 = sx, y1 = sy, x2 = ex, y2 = ey, kind = "char"}
+    end
+end
+
+for _, pair in ipairs({{"(", ")", {"(", ")", "b"}}, {"{", "}", {"{", "}", "B"}}, {"[", "]", {"[", "]"}}, {"<", ">", {"<lt>", "<gt>"}}}) do
+    local open, close, keys = pair[1], pair[2], pair[3]
+    for _, key in ipairs(keys) do
+        config:motion("i" .. key, pair_object(open, close, false), {object = true})
+        config:motion("a" .. key, pair_object(open, close, true), {object = true})
+    end
+end
+
+-- quotes pair up from the start of the line, the pair the cursor is in or the next one is used
+local function quote_object(q, around)
+    return function(pane)
+        local x, y = pane:get_cursor()
+        local quotes = {}
+        for i, c in ipairs(chars(pane, y)) do
+            if c == q then
+                quotes[#quotes + 1] = i - 1
+            end
+        end
+        for i = 1, #quotes - 1, 2 do
+            local s, e = quotes[i], quotes[i + 1]
+            if x <= e then
+                if around then
+                    return {x1 = s, y1 = y, x2 = e, y2 = y, kind = "char"}
+                elseif e == s + 1 then
+                    return nil
+                end
+                return {x1 = s + 1, y1 = y, x2 = e - 1, y2 = y, kind = "char"}
+            end
+        end
+        return nil
+    end
+end
+
+for _, q in ipairs({"\"", "'", "`"}) do
+    config:motion("i" .. q, quote_object(q, false), {object = true})
+    config:motion("a" .. q, quote_object(q, true), {object = true})
+end
+
+-- / and ? search forward and backward, n and N go to the next match the same or the other way
+config:key("n", "/", function()
+    config:cmdline("/")
+end)
+
+config:key("n", "?", function()
+    config:cmdline("?")
+end)
+
+config:motion("n", function(pane, count)
+    return pane:next_match(count)
+end)
+config:motion("N", function(pane, count)
+    return pane:next_match(count, true)
+end)
+
+-- * and # search for the word under the cursor
+local function search_word(backward)
+    local object = word_object(false, false)
+    return function(pane, count)
+        local r = object(pane)
+        if r == nil then
+            return nil
+        end
+        local word = pane:get_text(r)
+        if not word:find("%S") then
+            return nil
+        end
+        local pattern = (word:gsub("%p", "\\%0"))
+        if word:find("^[%w_]+$") then
+            pattern = "\\b" .. pattern .. "\\b"
+        end
+        config.search_pattern = pattern
+        config.search_backward = backward
+        return pane:next_match(count)
+    end
+end
+
+config:motion("*", search_word(false))
+config:motion("#", search_word(true))
+
+config:key("n", "x", function()
+    panes:get(0, function(pane)
+        local x, y = pane:get_cursor()
+        if pane:linelen(y) > 0 then
+            pane:delete({x1 = x, y1 = y, x2 = math.min(x + config.count, pane:linelen(y)) - 1, y2 = y, kind = "char"})
+        end
+    end)
+end)
+
+-- q{reg} records a macro and q stops it, @{reg} plays it and @@ plays the last one again
+config:key("n", "q", function()
+    if config.recording then
+        config:record()
+    else
+        config:getchar(function(c)
+            if c then
+                config:record(c)
+            end
+        end)
+    end
+end)
+
+config:key("n", "@", function()
+    local count = config.count
+    config:getchar(function(c)
+        if c then
+            config:play(c, count)
+        end
+    end)
+end)
+
+config:key("n", ".", function()
+    config:repeat_last()
+end)
+
+config:key("n", "p", function()
+    panes:get(0, function(pane)
+        pane:put(nil, false, config.count)
+    end)
+end)
+
+config:key("n", "P", function()
+    panes:get(0, function(pane)
+        pane:put(nil, true, config.count)
+    end)
+end)
+
+-- "a picks the register for the next yank, delete or put
+for c in ("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789\"-_+*"):gmatch(".") do
+    config:key("nv", "\"" .. c, function()
+        config.register = c
+    end)
+end
+
+config:key("n", "o", "$a<CR><Esc>")
+
+config:key("n", "O", "0i<CR><Esc>k")
+
+local function scroll(dx, dy)
+    return function()
+        panes:get(0, function(pane)
+            pane:scroll(dx*config.count, dy*config.count)
+        end)
+    end
+end
+
+config:key("nv", "<C-e>", scroll(0, 1))
+config:key("nv", "<C-y>", scroll(0, -1))
+config:key("nv", "zl", scroll(1, 0))
+config:key("nv", "zh", scroll(-1, 0))
+
+config:key("nv", "zz", function()
     panes:get(0, function(pane)
         local _, y = pane:get_cursor()
-        pane:set_cursor(0, y)
+        pane.top_line = math.max(y - pane.rows // 2, 0)
     end)
 end)
 
-config:key("n", "dd", function()
+config:key("nv", "zt", function()
+    panes:get(0, function(pane)
+        local _, y = pane:get_cursor()
+        pane.top_line = y
+    end)
+end)
+
+config:key("nv", "zb", function()
+    panes:get(0, function(pane)
+        local _, y = pane:get_cursor()
+        pane.top_line = math.max(y - pane.rows + 1, 0)
+    end)
+end)
+
+config:key("n", "u", function()
     panes:get(0, function(pane)
         for _ = 1, config.count, 1 do
-            pane:delete_line()
+            pane:undo()
         end
     end)
 end)
 
-config:key("n", "o", "$a<CR><Esc>")
+config:key("n", "<C-r>", function()
+    panes:get(0, function(pane)
+        for _ = 1, config.count, 1 do
+            pane:redo()
+        end
+    end)
+end)
 
-config:key("n", "O", "0i<CR><Esc>k")
+config:key("n", "g-", function()
+    panes:get(0, function(pane)
+        pane:earlier(config.count)
+    end)
+end)
+
+config:key("n", "g+", function()
+    panes:get(0, function(pane)
+        pane:later(config.count)
+    end)
+end)
+
+config:key("n", ":", function()
+    config:cmdline(":")
+end)
+
+config:key("v", ":", function()
+    config:cmdline(":", "'<,'>")
+end)
+
+local function write(opts)
+    panes:get(0, function(pane)
+        if opts.args ~= "" then
+            pane:save(opts.args)
+        else
+            pane:save()
+        end
+    end)
+end
+
+--closes the pane, the editor only quits once it's the last tiled one
+local function quit(opts)
+    local float = false
+    panes:get(0, function(pane)
+        if pane.modified and not opts.bang then
+            error("no write since last change (add ! to override)", 0)
+        end
+        float = pane.z_index ~= 0
+    end)
+    if float or panes.tiled > 1 then
+        panes:close(0)
+    else
+        config:quit()
+    end
+end
+
+config:command("w", write, {nargs = "?", complete = "file"})
+config:command("write", write, {nargs = "?", complete = "file"})
+config:command("q", quit, {bang = true})
+config:command("quit", quit, {bang = true})
+config:command("wq", function(opts)
+    write(opts)
+    quit(opts)
+end, {nargs = "?", bang = true, complete = "file"})
+
+local function edit(opts)
+    panes:get(0, function(pane)
+        pane:open(opts.args, opts.bang)
+    end)
+end
+
+config:command("e", edit, {nargs = 1, bang = true, complete = "file"})
+config:command("edit", edit, {nargs = 1, bang = true, complete = "file"})
 
+config:command("earlier", function(opts)
+    panes:get(0, function(pane)
+        pane:earlier(opts.args == "" and 1 or opts.args)
+    end)
+end, {nargs = "?"})
+
+config:command("later", function(opts)
+    panes:get(0, function(pane)
+        pane:later(opts.args == "" and 1 or opts.args)
+    end)
+end, {nargs = "?"})
+
+local function split(dir)
+    return function(opts)
+        panes:split(dir, opts.args ~= "" and opts.args or nil)
+    end
+end
+
+config:command("sp", split("h"), {nargs = "?", complete = "file"})
+config:command("split", split("h"), {nargs = "?", complete = "file"})
+config:command("vs", split("v"), {nargs = "?", complete = "file"})
+config:command("vsplit", split("v"), {nargs = "?", complete = "file"})
+-- :s/pattern/replacement/flags, any punctuation can stand in for the /
+-- an empty pattern uses the last search and :s on its own repeats the last substitute
+local last_substitute = nil
+
+local function substitute(opts)
+    local args = opts.args
+    local pattern, replacement, flags
+    if args == "" then
+        if last_substitute == nil then
+            error("no previous substitute", 0)
+        end
+        pattern, replacement, flags = last_substitute[1], last_substitute[2], ""
+    else
+        local delim = args:sub(1, 1)
+        if not delim:find("^%p$") or delim == "\\" or delim == "\"" or delim == "|" then
+            error("invalid delimiter " .. delim, 0)
+        end
+        local parts, cur, i = {}, "", 2
+        while i <= #args and #parts < 2 do
+            local c = args:sub(i, i)
+            if c == "\\" and args:sub(i + 1, i + 1) == delim then
+                cur = cur .. delim
+                i = i + 2
+            elseif c == "\\" then
+                cur = cur .. args:sub(i, i + 1)
+                i = i + 2
+            elseif c == delim then
+                table.insert(parts, cur)
+                cur = ""
+                i = i + 1
+            else
+                cur = cur .. c
+                i = i + 1
+            end
+        end
+        table.insert(parts, cur)
+        pattern, replacement = parts[1], parts[2] or ""
+        flags = #parts > 2 and args:sub(i) or ""
+        if pattern == "" then
+            pattern = config.search_pattern
+            if pattern == nil then
+                error("no previous regular expression", 0)
+            end
+        end
+        config.search_pattern = pattern
+        last_substitute = {pattern, replacement}
+    end
+    panes:get(0, function(pane)
+        -- commands get 1 based lines with line2 included, panes take 0 based ones with the end left out
+        if pane:substitute({opts.line1 - 1, opts.line2}, pattern, replacement, flags) == 0 then
+            error("pattern not found: " .. pattern, 0)
+        end
+    end)
+end
+
+config:command("s", substitute, {nargs = "*", range = true})
+config:command("substitute", substitute, {nargs = "*", range = true})
+
+config:command("colo", function(opts) config:colorscheme(opts.args) end, {nargs = 1})
+config:command("colorscheme", function(opts) config:colorscheme(opts.args) end, {nargs = 1})
+config:command("noh", function() config:nohlsearch() end)
+config:command("nohlsearch", function() config:nohlsearch() end)
+-- shows text in a float over the current pane, with the cursor at the end
+local function show_float(title, text)
+    local x, y, w, h
+    panes:get(0, function(pane)
+        x, y, w, h = pane:get_rect()
+    end)
+    local i = panes:open_float({x = x + 20, y = y + 40, w = math.max(w - 40, 1), h = math.max(h - 60, 1), title = title})
+    panes:get(i, function(pane)
+        pane:set_text({x1 = 0, y1 = 0, x2 = 0, y2 = 0, kind = "line"}, text)
+        pane:set_cursor(0, pane:line_count() - 1)
+    end)
+end
+
+-- the errors so far with their tracebacks
+local function messages()
+    local text = table.concat(config.messages, "\n\n")
+    if text ~= "" then
+        show_float("messages, :close to go back", text)
+    end
+end
+
+config:command("mes", messages)
+config:command("messages", messages)
+-- runs a lua file, without one the whole config is loaded again
+local function source(opts)
+    if opts.args ~= "" then
+        dofile(opts.args)
+    else
+        config:reload()
+    end
+end
+
+config:command("so", source, {nargs = "?", complete = "file"})
+config:command("source", source, {nargs = "?", complete = "file"})
+-- runs cargo with the args given, or cargo check, in the background and shows what it printed once it's done
+local function make(opts)
+    local args = {}
+    for arg in opts.args:gmatch("%S+") do
+        args[#args + 1] = arg
+    end
+    if #args == 0 then
+        args = {"check"}
+    end
+    local output = {}
+    local function collect(line)
+        output[#output + 1] = line
+    end
+    config:spawn({
+        cmd = "cargo",
+        args = args,
+        on_stdout = collect,
+        on_stderr = collect,
+        on_exit = function(code)
+            local title = "cargo " .. table.concat(args, " ") .. " exited with " .. (code or "a signal") .. ", :close to go back"
+            show_float(title, table.concat(output, "\n"))
+        end,
+    })
+end
+
+config:command("make", make, {nargs = "*"})
+config:command("clo", function() panes:close(0) end)
+config:command("close", function() panes:close(0) end)
+
+config:key("n", "<C-w>s", function()
+    panes:split("h")
+end)
+
+config:key("n", "<C-w>v", function()
+    panes:split("v")
+end)
+
+config:key("n", "<C-w>q", function()
+    quit({bang = false})
+end)
+
+config:key("n", "<C-w>c", function()
+    panes:close(0)
+end)
+
+config:key("n", "<C-w>w", function()
+    panes.current_pane = (panes.current_pane + config.count - 1) % panes.count + 1
+end)
+
+config:key("n", "<C-w>W", function()
+    panes.current_pane = (panes.current_pane - config.count - 1) % panes.count + 1
+end)
+
+config:key("n", "<C-w>+", function()
+    panes:resize(0, 0.05 * config.count)
+end)
+
+config:key("n", "<C-w>-", function()
+    panes:resize(0, -0.05 * config.count)
+end)
+
+local mode_names = {n = "NORMAL", i = "INSERT", v = "VISUAL", c = "COMMAND", o = "PENDING"}
+
+-- the file name with a slash on the end of each directory before it cut down to one letter, like s/main.rs
+local function short_path(path)
+    if path == nil then
+        return "[No Name]"
+    end
+    return (path:gsub("([^/])[^/]*/", "%1/"))
+end
+
+config.statusline = function(pane, current)
+    local left = {}
+    if current then
+        table.insert(left, {" " .. mode_names[config.mode] .. " ", hl = "Visual", bold = true})
+    end
+    table.insert(left, " " .. short_path(pane.path))
+    if pane.modified then
+        table.insert(left, " [+]")
+    end
+    local right = {}
+    if current then
+        if config.recording then
+            table.insert(right, "recording @" .. config.recording .. "  ")
+        end
+        if config.pending ~= "" then
+            table.insert(right, config.pending .. "  ")
+        end
+    end
+    if pane.filetype then
+        table.insert(right, pane.filetype .. "  ")
+    end
+    local x, y = pane:get_cursor()
+    table.insert(right, (y + 1) .. ":" .. (x + 1) .. " ")
+    return left, right
+end
+
+-- one tab per tiled pane, turned on with config.showtabline = true
+config.tabline = function()
+    local tabs = {}
+    for i = 1, panes.count do
+        panes:get(i, function(pane)
+            if pane.z_index == 0 then
+                local name = " " .. i .. " " .. short_path(pane.path) .. (pane.modified and " [+] " or " ")
+                table.insert(tabs, {name, hl = i == panes.current_pane and "TabLineSel" or "TabLine"})
+            end
+        end)
+    end
+    return tabs
+end
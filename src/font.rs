@@ -1,15 +1,55 @@
 //font manager
 pub type Font = (PathBuf, u16);
 use fontconfig::Fontconfig;
+use sdl2::{pixels::{Color, PixelFormatEnum}, rect::{Point, Rect}, render::{BlendMode, Texture}, ttf::FontStyle};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+type Canvas = sdl2::render::Canvas<sdl2::video::Window>;
 
+const ATLAS_SIZE: u32 = 1024;
+
+//glyphs are cached as white alpha masks and tinted with the color mod when drawn
+type GlyphKey = (Font, i32, char);
+
+struct Glyph {
+    atlas: usize,
+    rect: Option<Rect>,
+    size: (u32, u32),
+}
+
+//glyphs are packed into rows, a new row starts when the current one is full
+struct Atlas {
+    texture: Texture,
+    size: u32,
+    x: u32,
+    y: u32,
+    row: u32,
+}
+
+impl Atlas {
+    fn alloc(&mut self, w: u32, h: u32) -> Option<Rect> {
+        if self.x + w > self.size {
+            self.x = 0;
+            self.y += self.row;
+            self.row = 0;
+        }
+        if w > self.size || self.y + h > self.size {
+            return None;
+        }
+        let rect = Rect::new(self.x as i32, self.y as i32, w, h);
+        self.x += w;
+        self.row = self.row.max(h);
+        Some(rect)
+    }
+}
 
 pub struct Fonts {
     ttf: &'static sdl2::ttf::Sdl2TtfContext,
     fonts: HashMap<Font, sdl2::ttf::Font<'static, 'static>>,
     paths: HashMap<String, PathBuf>,
+    glyphs: HashMap<GlyphKey, Glyph>,
+    atlases: Vec<Atlas>,
 }
 
 
@@ -22,29 +62,22 @@ impl Fonts {
             ttf,
             fonts: HashMap::new(),
             paths: HashMap::new(),
+            glyphs: HashMap::new(),
+            atlases: Vec::new(),
         }
     }
+    //every line draws through here, so a cached path is returned before fontconfig gets asked anything
     pub fn find_font(&mut self, candidates: &[&str]) -> PathBuf {
-        let fc = Fontconfig::new().unwrap();
-        for &name in candidates {
-            if self.paths.contains_key(name) {
-                return self.paths.get(name).unwrap().clone();
-            }
-            if let Some(path) = fc.find(name, None) {
-                println!("found font {name}");
-                self.paths.insert(name.to_string(), path.path.clone());
-                return path.path;
-            }
-        }
-        panic!("no suitable font found from {:?}", candidates);
+        let name = self.find_font_exists(candidates);
+        self.paths[&name].clone()
     }
 
     pub fn find_font_exists(&mut self, candidates: &[&str]) -> String {
+        if let Some(&name) = candidates.iter().find(|&&name| self.paths.contains_key(name)) {
+            return name.to_string();
+        }
         let fc = Fontconfig::new().unwrap();
         for &name in candidates {
-            if self.paths.contains_key(name) {
-                return name.to_string();
-            }
             if let Some(path) = fc.find(name, None) {
                 println!("found font {name}");
                 self.paths.insert(name.to_string(), path.path);
@@ -63,4 +96,58 @@ impl Fonts {
 
         self.fonts.get_mut(&font).unwrap()
     }
+    //size of `ch` in `font`, without touching the atlas
+    pub fn char_size(&mut self, font: &Font, style: FontStyle, ch: char) -> (u32, u32) {
+        if let Some(glyph) = self.glyphs.get(&(font.clone(), style.bits(), ch)) {
+            return glyph.size;
+        }
+        let f = self.load_font(font);
+        f.set_style(style);
+        f.size_of_char(ch).unwrap_or((0, f.height() as u32))
+    }
+    pub fn draw_char(&mut self, canvas: &mut Canvas, font: &Font, style: FontStyle, ch: char, color: Color, at: Point) {
+        let key = (font.clone(), style.bits(), ch);
+        if !self.glyphs.contains_key(&key) {
+            let glyph = self.rasterize(canvas, font, style, ch);
+            self.glyphs.insert(key.clone(), glyph);
+        }
+        let glyph = &self.glyphs[&key];
+        let Some(src) = glyph.rect else {
+            return;
+        };
+        let atlas = &mut self.atlases[glyph.atlas];
+        atlas.texture.set_color_mod(color.r, color.g, color.b);
+        atlas.texture.set_alpha_mod(color.a);
+        let _ = canvas.copy(&atlas.texture, src, Rect::new(at.x, at.y, src.width(), src.height()));
+    }
+    fn rasterize(&mut self, canvas: &mut Canvas, font: &Font, style: FontStyle, ch: char) -> Glyph {
+        let f = self.load_font(font);
+        f.set_style(style);
+        let size = f.size_of_char(ch).unwrap_or((0, f.height() as u32));
+        let surface = f.render_char(ch).blended(Color::WHITE).ok()
+            .and_then(|s| s.convert_format(PixelFormatEnum::ARGB8888).ok());
+        let Some(surface) = surface else {
+            return Glyph { atlas: 0, rect: None, size };
+        };
+        let (w, h) = surface.size();
+        if w == 0 || h == 0 {
+            return Glyph { atlas: 0, rect: None, size };
+        }
+
+        let mut place = self.atlases.last_mut().and_then(|a| a.alloc(w, h));
+        if place.is_none() {
+            let size = ATLAS_SIZE.max(w).max(h);
+            let mut texture = canvas.create_texture_static(PixelFormatEnum::ARGB8888, size, size).unwrap();
+            texture.set_blend_mode(BlendMode::Blend);
+            self.atlases.push(Atlas { texture, size, x: 0, y: 0, row: 0 });
+            place = self.atlases.last_mut().unwrap().alloc(w, h);
+        }
+        let atlas = self.atlases.len() - 1;
+        let rect = place.unwrap();
+        let pitch = surface.pitch() as usize;
+        surface.with_lock(|pixels| {
+            self.atlases[atlas].texture.update(rect, pixels, pitch).unwrap();
+        });
+        Glyph { atlas, rect: Some(rect), size }
+    }
 }
@@ -0,0 +1,222 @@
+//timers and child processes for lua, the callbacks of both run on the main loop between keys
+//so they can touch panes and config like a keymap can
+use std::{collections::HashMap, io::{BufRead, BufReader, Read}, process::{Child, Command, Stdio}, sync::{mpsc::{self, Receiver, Sender}, Arc, Mutex}, thread, time::{Duration, Instant}};
+
+use mlua::{Function, Table};
+use sdl2::event::{Event, EventSender};
+
+use crate::Config;
+
+struct Timer {
+    id: usize,
+    due: Instant,
+    //repeating timers are pushed back by this much every time they fire
+    every: Option<Duration>,
+    func: Function,
+}
+
+struct Job {
+    child: Arc<Mutex<Child>>,
+    on_stdout: Option<Function>,
+    on_stderr: Option<Function>,
+    on_exit: Option<Function>,
+}
+
+impl Job {
+    fn callback(&self, output: &Output) -> Option<Function> {
+        match output {
+            Output::Stdout(_) => self.on_stdout.clone(),
+            Output::Stderr(_) => self.on_stderr.clone(),
+            Output::Exit(_) => self.on_exit.clone(),
+        }
+    }
+}
+
+//what the threads reading a job send back
+enum Output {
+    Stdout(String),
+    Stderr(String),
+    //the exit code, None when a signal ended it
+    Exit(Option<i32>),
+}
+
+pub struct Jobs {
+    timers: Vec<Timer>,
+    jobs: HashMap<usize, Job>,
+    //timers and jobs share ids so cancel works on either
+    next_id: usize,
+    sender: Sender<(usize, Output)>,
+    receiver: Receiver<(usize, Output)>,
+    //wakes the main loop when output comes in, set once the event loop is up
+    waker: Option<(Arc<EventSender>, u32)>,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { timers: Vec::new(), jobs: HashMap::new(), next_id: 1, sender, receiver, waker: None }
+    }
+    pub fn set_waker(&mut self, sender: EventSender, kind: u32) {
+        self.waker = Some((Arc::new(sender), kind));
+    }
+    pub fn add_timer(&mut self, ms: u64, every: bool, func: Function) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let interval = Duration::from_millis(ms);
+        self.timers.push(Timer { id, due: Instant::now() + interval, every: every.then_some(interval), func });
+        id
+    }
+    //timers belong to the config that made them, so they go when it's reloaded while jobs finish
+    pub fn clear_timers(&mut self) {
+        self.timers.clear();
+    }
+    //opts are cmd, args, cwd, on_stdout, on_stderr and on_exit, the output callbacks get a line at a time
+    pub fn spawn(&mut self, opts: Table) -> mlua::Result<usize> {
+        let cmd: String = opts.get("cmd")?;
+        let args: Vec<String> = opts.get::<Option<_>>("args")?.unwrap_or_default();
+        let mut command = Command::new(&cmd);
+        command.args(&args).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        if let Some(cwd) = opts.get::<Option<String>>("cwd")? {
+            command.current_dir(cwd);
+        }
+        let mut child = command.spawn().map_err(|e| mlua::Error::runtime(format!("can't run {cmd}: {e}")))?;
+        let id = self.next_id;
+        self.next_id += 1;
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        let child = Arc::new(Mutex::new(child));
+        let send = {
+            let sender = self.sender.clone();
+            let waker = self.waker.clone();
+            move |output: Output| {
+                let _ = sender.send((id, output));
+                if let Some((waker, kind)) = &waker {
+                    wake(waker, *kind);
+                }
+            }
+        };
+        let errors = {
+            let send = send.clone();
+            thread::spawn(move || read_lines(stderr, |line| send(Output::Stderr(line))))
+        };
+        let waiting = child.clone();
+        thread::spawn(move || {
+            read_lines(stdout, |line| send(Output::Stdout(line)));
+            let _ = errors.join();
+            //both pipes are closed, but the process can outlive them, and cancel needs the lock now and then
+            let code = loop {
+                match waiting.lock().unwrap().try_wait() {
+                    Ok(Some(status)) => break status.code(),
+                    Ok(None) => {}
+                    Err(_) => break None,
+                }
+                thread::sleep(Duration::from_millis(10));
+            };
+            send(Output::Exit(code));
+        });
+        self.jobs.insert(id, Job {
+            child,
+            on_stdout: opts.get("on_stdout")?,
+            on_stderr: opts.get("on_stderr")?,
+            on_exit: opts.get("on_exit")?,
+        });
+        Ok(id)
+    }
+    //stops a timer or kills a job, a killed job still gets its on_exit
+    pub fn cancel(&mut self, id: usize) -> bool {
+        if let Some(job) = self.jobs.get(&id) {
+            let _ = job.child.lock().unwrap().kill();
+            return true;
+        }
+        let len = self.timers.len();
+        self.timers.retain(|t| t.id != id);
+        self.timers.len() != len
+    }
+    //how long until the next timer is due, for the main loop to wait no longer than that
+    pub fn timeout(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.timers.iter().map(|t| t.due.saturating_duration_since(now)).min()
+    }
+}
+
+impl Drop for Jobs {
+    fn drop(&mut self) {
+        for job in self.jobs.values() {
+            let _ = job.child.lock().unwrap().kill();
+        }
+    }
+}
+
+pub fn wake(sender: &EventSender, kind: u32) {
+    let _ = sender.push_event(Event::User {
+        timestamp: 0,
+        window_id: 0,
+        type_: kind,
+        code: 0,
+        data1: std::ptr::null_mut(),
+        data2: std::ptr::null_mut(),
+    });
+}
+
+//calls `f` with every line read from `from`, without the line break
+fn read_lines(from: impl Read, mut f: impl FnMut(String)) {
+    let mut reader = BufReader::new(from);
+    let mut line = Vec::new();
+    while let Ok(n) = reader.read_until(b'\n', &mut line) {
+        if n == 0 {
+            break;
+        }
+        if line.ends_with(b"\n") {
+            line.pop();
+        }
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+        f(String::from_utf8_lossy(&line).into_owned());
+        line.clear();
+    }
+}
+
+//calls the timers that are due and the callbacks for what jobs sent since the last time round,
+//returns whether anything ran
+pub fn run(config: &mut Config) -> bool {
+    let now = Instant::now();
+    let mut calls: Vec<(Function, Option<Output>)> = Vec::new();
+    let jobs = &mut config.jobs;
+    jobs.timers.retain_mut(|timer| {
+        if timer.due > now {
+            return true;
+        }
+        calls.push((timer.func.clone(), None));
+        match timer.every {
+            //a timer that fell behind doesn't try to catch up
+            Some(every) => {
+                timer.due = (timer.due + every).max(now);
+                true
+            }
+            None => false,
+        }
+    });
+    while let Ok((id, output)) = jobs.receiver.try_recv() {
+        let func = jobs.jobs.get(&id).and_then(|job| job.callback(&output));
+        if let Output::Exit(_) = output {
+            jobs.jobs.remove(&id);
+        }
+        if let Some(func) = func {
+            calls.push((func, Some(output)));
+        }
+    }
+    let ran = !calls.is_empty();
+    //nothing in config is borrowed by now, so the callbacks are free to start and cancel more
+    for (func, output) in calls {
+        let result = match output {
+            None => func.call::<()>(()),
+            Some(Output::Stdout(line) | Output::Stderr(line)) => func.call::<()>(line),
+            Some(Output::Exit(code)) => func.call::<()>(code),
+        };
+        if let Err(e) = result {
+            config.cmdline.error(e);
+        }
+    }
+    ran
+}
@@ -9,37 +9,70 @@ use crate::pane::Mode;
 pub enum Action {
     Function(Function),
     Macro(String),
+    Operator(Function),
+    Motion(Motion),
 }
 
-pub fn parse_keys(input: &str, leader:char) -> Vec<Key> {
+//a motion gives a position to move to, or a region for text objects like iw
+#[derive(Debug, Clone)]
+pub struct Motion {
+    pub func: Function,
+    //linewise motions like j work on whole lines when an operator uses them
+    pub linewise: bool,
+    //whether the char the motion lands on is part of the region, like e but unlike w
+    pub inclusive: bool,
+    //motions like f take the next typed char as an argument
+    pub char: bool,
+}
+
+//what a key sequence asked for that needs the panes, the main loop runs these
+#[derive(Debug)]
+pub enum Ready {
+    //an operator waiting for its motion
+    Operator(Function, Option<usize>),
+    //an operator typed twice, like dd, runs on count lines
+    Lines(Option<usize>),
+    //an operator typed in visual mode runs on the selection
+    Visual(Function),
+    Motion(Motion, Option<usize>, Option<char>),
+    Cancel,
+}
+
+//a mistake in the notation is an error rather than a panic, since it comes straight from init.lua
+pub fn parse_keys(input: &str, leader:char) -> Result<Vec<Key>, String> {
     let mut out = Vec::new();
     let mut chars = input.chars().peekable();
 
     while let Some(c) = chars.next() {
         if c == '<' {
             let mut name = String::new();
+            let mut closed = false;
             while let Some(&ch) = chars.peek() {
                 chars.next();
                 if ch == '>' {
+                    closed = true;
                     break;
                 }
                 name.push(ch);
             }
-            out.push(parse_section(&name.to_lowercase(), leader))
+            if !closed {
+                return Err(format!("missing > after <{name} in {input:?}"));
+            }
+            out.push(parse_section(&name.to_lowercase(), leader).map_err(|e| format!("{e} in {input:?}"))?)
         } else {
             out.push(Key{
-                key: Keys::Char(c.to_lowercase().nth(0).unwrap()),
+                key: Keys::Char(c.to_lowercase().next().unwrap()),
                 shift: c.is_uppercase(),
                 ..Default::default()
             })
         }
     }
 
-    return out
+    Ok(out)
 }
 
-pub fn parse_section(name: &str, leader: char) -> Key {
-    match name {
+pub fn parse_section(name: &str, leader: char) -> Result<Key, String> {
+    Ok(match name {
         "gt"=> Key{
             key: Keys::Char('>'),
             ..Default::default()
@@ -104,21 +137,21 @@ pub fn parse_section(name: &str, leader: char) -> Key {
                 }
                 if c||s||a {
                     let rest = name.chars().skip(2).collect::<String>();
-                    let mut key = parse_section(&rest, leader);
+                    let mut key = parse_section(&rest, leader)?;
                     key.alt = key.alt || a;
                     key.shift = key.shift || s;
                     key.ctrl = key.ctrl || c;
-                    return key;
+                    return Ok(key);
                 }
-                panic!("unknown key <{}>", other)
+                return Err(format!("unknown key <{other}>"));
             }
             Key {
-                key:Keys::Char(name.chars().nth(0).unwrap()),
+                key:Keys::Char(name.chars().next().ok_or("missing key name in <>")?),
                 ..Default::default()
             }
 
         },
-    }
+    })
 }
 
 #[derive(Hash, Eq, PartialEq, Default, Clone, Debug)]
@@ -132,6 +165,60 @@ pub struct Key{
 type Event = (Keycode, Mod, Option<String>, bool);
 
 impl Key {
+    fn from_event(event: &Event) -> Self {
+        let (key, keymod, text, _) = event;
+        let ctrl  = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+        let mut shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+        let alt   = keymod.intersects(Mod::LALTMOD | Mod::RALTMOD);
+
+        if keymod.intersects(Mod::CAPSMOD) {
+            shift = !shift;
+        }
+
+        Key {
+            key: Keys::from(*key, text.clone()),
+            ctrl,
+            shift,
+            alt,
+        }
+    }
+    //the key written the way parse_keys reads it, like a, A, <lt> or <C-r>
+    fn notation(&self) -> Option<String> {
+        let name = match self.key {
+            Keys::Char(c) if !self.ctrl && !self.alt => {
+                return Some(match c {
+                    '<' => "<lt>".to_string(),
+                    c if self.shift => c.to_uppercase().to_string(),
+                    c => c.to_string(),
+                });
+            }
+            Keys::Char('<') => "lt".to_string(),
+            Keys::Char(' ') => "Space".to_string(),
+            Keys::Char(c) => c.to_string(),
+            Keys::Esc => "Esc".to_string(),
+            Keys::Tab => "Tab".to_string(),
+            Keys::CR => "CR".to_string(),
+            Keys::Backspace => "BS".to_string(),
+            Keys::Left => "Left".to_string(),
+            Keys::Right => "Right".to_string(),
+            Keys::Up => "Up".to_string(),
+            Keys::Down => "Down".to_string(),
+            Keys::Unknown | Keys::Finish => return None,
+        };
+        let mut out = "<".to_string();
+        if self.ctrl {
+            out.push_str("C-");
+        }
+        if self.alt {
+            out.push_str("A-");
+        }
+        if self.shift {
+            out.push_str("S-");
+        }
+        out.push_str(&name);
+        out.push('>');
+        Some(out)
+    }
     fn finish() -> Self {
         Self {
             alt: false,
@@ -140,7 +227,7 @@ impl Key {
             key: Keys::Finish,
         }
     }
-    fn to_event(&self) -> Event {
+    fn to_event(&self) -> Result<Event, String> {
         let mut keymod = Mod::NOMOD;
         if self.alt {
             keymod = keymod|Mod::LALTMOD;    
@@ -162,14 +249,14 @@ impl Key {
             Keys::Right => Keycode::Right,
             Keys::Down => Keycode::Down,
             Keys::Backspace => Keycode::Backspace,
-            Keys::Unknown => panic!("nope"),
+            Keys::Unknown => return Err("unknown key".to_string()),
             Keys::Finish => {
                 finish = true;
                 Keycode::KP_0
             },
             Keys::Char(' ') => Keycode::Space,
             Keys::Char(c) => {
-                text = Some(c.to_string());
+                text = Some(if self.shift { c.to_uppercase().to_string() } else { c.to_string() });
                 if let Some(s) = Keycode::from_name(&c.to_string()) {
                     s
                 } else {
@@ -177,7 +264,7 @@ impl Key {
                 }
             }
         };
-        return (keycode, keymod, text, finish);
+        Ok((keycode, keymod, text, finish))
     }
 }
 
@@ -213,6 +300,12 @@ impl Keys {
                if let Some(c) = text {
                    return Keys::Char(c.to_lowercase().chars().nth(0).unwrap());
                }
+               //no text comes with ctrl and alt chords, so fall back to the key's name
+               let name = value.name();
+               let mut chars = name.chars();
+               if let (Some(c), None) = (chars.next(), chars.next()) {
+                   return Keys::Char(c.to_lowercase().nth(0).unwrap());
+               }
                return Self::Unknown;
            }
        } 
@@ -233,6 +326,30 @@ pub struct Keymaps {
     pos: Vec<Key>,
     pub count: String,
     pub events: Vec<Event>,
+    //the operator waiting in operator pending mode and its count
+    pub operator: Option<(Function, Option<usize>)>,
+    //a motion like f that is waiting for its char
+    char_motion: Option<(Motion, Option<usize>)>,
+    pub ready: Option<Ready>,
+    //how many events at the front of the queue came from macros or repeats, those aren't recorded
+    replayed: usize,
+    //whether the last key went into the count
+    counted: bool,
+    //the keys of the change being typed with the count digits taken out, and the count
+    change: Vec<Event>,
+    change_count: Option<usize>,
+    digits: String,
+    //whether the text changed since the change started, and whether to throw it away anyway
+    change_dirty: bool,
+    change_discard: bool,
+    last_change: Option<(Vec<Event>, Option<usize>)>,
+    //the register a macro is being recorded into and the keys typed so far
+    recording: Option<(char, Vec<Event>)>,
+    pub last_played: Option<char>,
+    //called with the next typed char, for things like q and @ that take a register
+    pub char_callback: Option<Function>,
+    //errors from lua functions the keys ran, the main loop shows them
+    pub errors: Vec<mlua::Error>,
 }
 
 impl Keymaps {
@@ -243,38 +360,57 @@ impl Keymaps {
             pos: Vec::new(),
             events: Vec::new(),
             count:String::new(),
+            operator: None,
+            char_motion: None,
+            ready: None,
+            replayed: 0,
+            counted: false,
+            change: Vec::new(),
+            change_count: None,
+            digits: String::new(),
+            change_dirty: false,
+            change_discard: false,
+            last_change: None,
+            recording: None,
+            last_played: None,
+            char_callback: None,
+            errors: Vec::new(),
         }
     }
-    pub fn set(&mut self, mode: String, keys: String, func: Action, leader: char) {
-        let modes: Vec<Mode> = mode.chars().map(Mode::from).collect();
+    pub fn set(&mut self, mode: String, keys: String, func: Action, leader: char) -> Result<(), String> {
+        let modes = mode.chars().map(Mode::try_from).collect::<Result<Vec<Mode>, String>>()?;
         for mode in modes {
             if !self.keymaps.contains_key(&mode) {
                 self.keymaps.insert(mode.clone(), Keymap::new());
             } 
-            self.keymaps.get_mut(&mode).unwrap().set(keys.clone(), func.clone(), leader);
+            self.keymaps.get_mut(&mode).unwrap().set(keys.clone(), func.clone(), leader)?;
         }
+        Ok(())
     }
     //TASK(20260112-210317-316-n6-047): make leader work
     pub fn handle(&mut self, mode: Mode, key: Keycode, keymod: Mod, text: Option<String>, finish: bool) {
-        let ctrl  = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
-        let mut shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
-        let alt   = keymod.intersects(Mod::LALTMOD | Mod::RALTMOD);
-
-        if keymod.intersects(Mod::CAPSMOD) {
-            shift = !shift;
+        if let Some(f) = self.char_callback.take() {
+            let c = text.and_then(|t| t.chars().next()).filter(|_| key != Keycode::Escape);
+            if let Err(e) = f.call::<()>(c.map(String::from)) {
+                self.errors.push(e);
+            }
+            return;
         }
-
-        let key = Key {
-            key: Keys::from(key, text),
-            ctrl,
-            shift,
-            alt,
-        };
+        if let Some((motion, count)) = self.char_motion.take() {
+            match text.and_then(|t| t.chars().next()) {
+                Some(c) if key != Keycode::Escape => self.ready = Some(Ready::Motion(motion, count, Some(c))),
+                _ if mode == Mode::Operator => self.ready = Some(Ready::Cancel),
+                _ => {}
+            }
+            return;
+        }
+        let key = Key::from_event(&(key, keymod, text, finish));
         if let Keys::Char(v) = key.key {
             if v.is_numeric() {
                 if !(v == '0' && self.count.len() == 0) {
                     self.count.push(v); 
                     self.last = Some(Instant::now());
+                    self.counted = true;
                     return;
                 }
             }
@@ -290,6 +426,9 @@ impl Keymaps {
             self.pos.clear();
             self.count = "".to_string();
             self.last = None;
+            if mode == Mode::Operator {
+                self.ready = Some(Ready::Cancel);
+            }
             return;
         };
 
@@ -321,41 +460,165 @@ impl Keymaps {
             self.pos.clear();
             self.last = None;
             exit = false;
-            match func {
-                Action::Function(f) => {
-                    f.call::<()>(()).unwrap();
-                }
-                Action::Macro(m) => {
-                    self.call_macro(m);
-                }
-            }
+            self.run(&mode, func);
             self.count = "".to_string();
         }
         if exit {
             self.pos.clear();
             self.count = "".to_string();
             self.last = None;
+            if mode == Mode::Operator {
+                self.ready = Some(Ready::Cancel);
+            }
         }
     }
-    pub fn call_macro(&mut self, m: String) {
-        let keys = parse_keys(&m, ' ');
-        let mut event: Vec<Event> = keys.iter().map(|v| v.to_event()).collect();
-        event.push(Key::finish().to_event());
+    fn run(&mut self, mode: &Mode, action: Action) {
+        let count = self.count.parse::<usize>().ok();
+        match action {
+            Action::Function(f) => {
+                if let Err(e) = f.call::<()>(()) {
+                    self.errors.push(e);
+                }
+            }
+            Action::Macro(m) => {
+                if let Err(e) = self.call_macro(m) {
+                    self.errors.push(mlua::Error::runtime(e));
+                }
+            }
+            Action::Operator(f) => {
+                self.ready = Some(match (mode, &self.operator) {
+                    (Mode::Operator, Some((op, _))) if *op == f => Ready::Lines(count),
+                    (Mode::Operator, _) => Ready::Cancel,
+                    (Mode::Visual, _) => Ready::Visual(f),
+                    _ => Ready::Operator(f, count),
+                });
+            }
+            Action::Motion(m) => {
+                if m.char {
+                    self.char_motion = Some((m, count));
+                } else {
+                    self.ready = Some(Ready::Motion(m, count, None));
+                }
+            }
+        }
+    }
+    pub fn call_macro(&mut self, m: String) -> Result<(), String> {
+        let keys = parse_keys(&m, ' ')?;
+        let mut event: Vec<Event> = keys.iter().map(|v| v.to_event()).collect::<Result<_, _>>()?;
+        event.push(Key::finish().to_event()?);
+        self.replayed += event.len();
         self.events.splice(0..0, event);
+        Ok(())
+    }
+
+    //pops the next event, the bool is whether it was typed rather than replayed
+    pub fn next_event(&mut self) -> Option<(Event, bool)> {
+        let event = crate::first(&mut self.events)?;
+        let typed = self.replayed == 0;
+        self.replayed = self.replayed.saturating_sub(1);
+        if typed && let Some((_, keys)) = &mut self.recording {
+            keys.push(event.clone());
+        }
+        Some((event, typed))
+    }
+    //the count and keys typed so far of a sequence that isn't finished yet
+    pub fn pending(&self) -> String {
+        let keys: String = self.pos.iter().filter_map(Key::notation).collect();
+        format!("{}{keys}", self.count)
+    }
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(c, _)| *c)
+    }
+    pub fn start_recording(&mut self, reg: char) {
+        self.recording = Some((reg, Vec::new()));
+    }
+    //returns the register and the macro in key notation, the key that stopped the recording is left out
+    pub fn stop_recording(&mut self) -> Option<(char, String)> {
+        let (reg, mut keys) = self.recording.take()?;
+        keys.pop();
+        let text = keys.iter().filter_map(|e| Key::from_event(e).notation()).collect();
+        Some((reg, text))
+    }
+    //adds a typed key to the change being recorded for ., `mode` is the mode it was typed in
+    pub fn record(&mut self, event: Event, mode: &Mode, typed: bool) {
+        let counted = std::mem::take(&mut self.counted);
+        if !typed {
+            return;
+        }
+        if *mode == Mode::Command {
+            //changes made from the command line aren't repeated with .
+            self.change_discard = true;
+        }
+        if counted {
+            if let Some(text) = &event.2 {
+                self.digits.push_str(text);
+            }
+            return;
+        }
+        if let Ok(n) = self.digits.parse::<usize>() {
+            self.change_count = Some(self.change_count.unwrap_or(1) * n);
+        }
+        self.digits.clear();
+        self.change.push(event);
+    }
+    //a change ends once everything it typed has run and the editor is back in normal mode
+    pub fn end_change(&mut self, mode: &Mode, changed: bool) {
+        self.change_dirty |= changed;
+        let idle = self.pos.is_empty() && self.count.is_empty() && self.operator.is_none()
+            && self.char_motion.is_none() && self.ready.is_none() && self.replayed == 0;
+        if *mode != Mode::Normal || !idle {
+            return;
+        }
+        if self.change_dirty && !self.change_discard && !self.change.is_empty() {
+            self.last_change = Some((std::mem::take(&mut self.change), self.change_count));
+        }
+        self.change.clear();
+        self.change_count = None;
+        self.digits.clear();
+        self.change_dirty = false;
+        self.change_discard = false;
+    }
+    //replays the last change, a count typed before . replaces the one it had
+    pub fn repeat_last(&mut self) {
+        let Some((change, count)) = self.last_change.clone() else {
+            return;
+        };
+        let count = self.count.parse::<usize>().ok().or(count);
+        //digits and finish always have an event
+        let mut events: Vec<Event> = count.map(|n| n.to_string()).unwrap_or_default().chars().flat_map(|c| Key {
+            key: Keys::Char(c),
+            ..Default::default()
+        }.to_event()).collect();
+        events.extend(change);
+        events.extend(Key::finish().to_event());
+        //the . itself isn't a change
+        self.change_discard = true;
+        self.replayed += events.len();
+        self.events.splice(0..0, events);
     }
 
-    pub fn handle_timeout(&mut self, mode: Mode, timeout: u64) {
-        let Some(start_time) = self.last else { return; };
+    //how long until the pending key sequence times out
+    pub fn timeout_in(&self, timeout: u64) -> Option<Duration> {
+        let start_time = self.last?;
+        Some((start_time + Duration::from_millis(timeout)).saturating_duration_since(Instant::now()))
+    }
+
+    //returns whether the pending sequence timed out
+    pub fn handle_timeout(&mut self, mode: Mode, timeout: u64) -> bool {
+        let Some(start_time) = self.last else { return false; };
 
         let Some(mut s) = self.keymaps.get(&mode) else {
             self.pos.clear();
             self.count = "".to_string();
             self.last = None;
-            return;
+            if mode == Mode::Operator {
+                self.ready = Some(Ready::Cancel);
+            }
+            return true;
         };
 
         if Instant::now() < start_time + Duration::from_millis(timeout) {
-            return;
+            return false;
         }
 
         let mut action_to_call = None;
@@ -381,16 +644,12 @@ impl Keymaps {
         self.last = None;
 
         if let Some(func) = action_to_call {
-            match func {
-                Action::Function(f) => {
-                    f.call::<()>(()).unwrap();
-                }
-                Action::Macro(m) => {
-                    self.call_macro(m);
-                }
-            }
+            self.run(&mode, func);
+        } else if mode == Mode::Operator {
+            self.ready = Some(Ready::Cancel);
         }
         self.count = "".to_string();
+        true
     }
 }
 
@@ -409,10 +668,10 @@ impl Keymap {
             child: HashMap::new(),
         }
     }
-    pub fn set(&mut self, keys: String, func: Action, leader: char) {
-        let keys = parse_keys(&keys, leader);
+    pub fn set(&mut self, keys: String, func: Action, leader: char) -> Result<(), String> {
+        let keys = parse_keys(&keys, leader)?;
         if keys.len() == 0 {
-            return
+            return Ok(())
         }
         let mut s = self;
         for key in keys {
@@ -422,5 +681,6 @@ impl Keymap {
             s = s.child.get_mut(&key).unwrap();
         }
         s.action = Some(func);
+        Ok(())
     }
 }
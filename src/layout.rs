@@ -0,0 +1,141 @@
+//tiling layout, a tree of splits whose leaves are indices into Panes::panes
+use sdl2::rect::Rect;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    //panes stacked on top of each other, like :split
+    Horizontal,
+    //panes side by side, like :vsplit
+    Vertical,
+}
+
+#[derive(Debug)]
+pub enum Layout {
+    Leaf(usize),
+    //each child has the share of the space it takes up, the shares add up to 1
+    Split(Direction, Vec<(f32, Layout)>),
+}
+
+impl Layout {
+    //works out the rect of every pane and of the dividers between them
+    pub fn rects(&self, rect: Rect, gap: u32, out: &mut Vec<(usize, Rect)>, dividers: &mut Vec<Rect>) {
+        match self {
+            Layout::Leaf(i) => out.push((*i, rect)),
+            Layout::Split(dir, children) => {
+                let len = match dir {
+                    Direction::Horizontal => rect.height(),
+                    Direction::Vertical => rect.width(),
+                };
+                let total = len.saturating_sub(gap * (children.len() as u32 - 1));
+                let mut pos = 0;
+                for (n, (share, child)) in children.iter().enumerate() {
+                    let size = if n + 1 == children.len() {
+                        total.saturating_sub(pos)
+                    } else {
+                        ((total as f32 * share) as u32).min(total.saturating_sub(pos))
+                    };
+                    let offset = pos + gap * n as u32;
+                    let (r, divider) = match dir {
+                        Direction::Horizontal => (
+                            Rect::new(rect.x, rect.y + offset as i32, rect.width(), size.max(1)),
+                            Rect::new(rect.x, rect.y + (offset + size) as i32, rect.width(), gap.max(1)),
+                        ),
+                        Direction::Vertical => (
+                            Rect::new(rect.x + offset as i32, rect.y, size.max(1), rect.height()),
+                            Rect::new(rect.x + (offset + size) as i32, rect.y, gap.max(1), rect.height()),
+                        ),
+                    };
+                    child.rects(r, gap, out, dividers);
+                    if n + 1 != children.len() {
+                        dividers.push(divider);
+                    }
+                    pos += size;
+                }
+            }
+        }
+    }
+    fn child_pos(children: &[(f32, Layout)], target: usize) -> Option<usize> {
+        children.iter().position(|(_, c)| matches!(c, Layout::Leaf(i) if *i == target))
+    }
+    //puts `new` next to `target`, splitting the space `target` had
+    pub fn split(&mut self, target: usize, new: usize, dir: Direction) -> bool {
+        match self {
+            Layout::Leaf(i) if *i == target => {
+                *self = Layout::Split(dir, vec![(0.5, Layout::Leaf(target)), (0.5, Layout::Leaf(new))]);
+                true
+            }
+            Layout::Leaf(_) => false,
+            Layout::Split(d, children) => {
+                if let Some(pos) = Self::child_pos(children, target) && *d == dir {
+                    let share = children[pos].0 / 2.0;
+                    children[pos].0 = share;
+                    children.insert(pos + 1, (share, Layout::Leaf(new)));
+                    return true;
+                }
+                children.iter_mut().any(|(_, c)| c.split(target, new, dir))
+            }
+        }
+    }
+    //the pane in the top left, where focus goes when it has nowhere better to be
+    pub fn first(&self) -> usize {
+        match self {
+            Layout::Leaf(i) => *i,
+            Layout::Split(_, children) => children[0].1.first(),
+        }
+    }
+    //takes the pane out of the layout, its space goes to a neighbour,
+    //gives back the pane in that neighbour for focus to move to
+    pub fn remove(&mut self, target: usize) -> Option<usize> {
+        let Layout::Split(_, children) = self else {
+            return None;
+        };
+        if let Some(pos) = Self::child_pos(children, target) {
+            let (share, _) = children.remove(pos);
+            let neighbour = pos.min(children.len() - 1);
+            children[neighbour].0 += share;
+            let focus = children[neighbour].1.first();
+            if children.len() == 1 {
+                *self = children.pop().unwrap().1;
+            }
+            return Some(focus);
+        }
+        children.iter_mut().find_map(|(_, c)| c.remove(target))
+    }
+    //the pane indices after a removed one shift down by one
+    pub fn renumber(&mut self, removed: usize) {
+        match self {
+            Layout::Leaf(i) => {
+                if *i > removed {
+                    *i -= 1;
+                }
+            }
+            Layout::Split(_, children) => {
+                for (_, c) in children.iter_mut() {
+                    c.renumber(removed);
+                }
+            }
+        }
+    }
+    //grows the pane's share of its split by `delta`, taking it from the pane after it
+    pub fn resize(&mut self, target: usize, delta: f32) -> bool {
+        let Layout::Split(_, children) = self else {
+            return false;
+        };
+        if let Some(pos) = Self::child_pos(children, target) {
+            if children.len() < 2 {
+                return true;
+            }
+            let other = if pos + 1 < children.len() { pos + 1 } else { pos - 1 };
+            const MIN: f32 = 0.05;
+            let (lo, hi) = (MIN - children[pos].0, children[other].0 - MIN);
+            if lo > hi {
+                return true;
+            }
+            let delta = delta.clamp(lo, hi);
+            children[pos].0 += delta;
+            children[other].0 -= delta;
+            return true;
+        }
+        children.iter_mut().any(|(_, c)| c.resize(target, delta))
+    }
+}
@@ -1,6 +1,21 @@
+mod autocmd;
 mod pane;
+mod text;
+mod undo;
 mod font;
+mod jobs;
 mod keymap;
+mod cmdline;
+mod layout;
+mod operator;
+mod register;
+mod reload;
+mod search;
+mod statusline;
+mod substitute;
+mod syntax;
+mod theme;
+mod wrap;
 
 fn first<T>(vec: &mut Vec<T>) -> Option<T> {
     if vec.len() == 0 {
@@ -11,20 +26,29 @@ fn first<T>(vec: &mut Vec<T>) -> Option<T> {
 
 use keymap::Keymaps;
 
-use std::path::PathBuf;
+use std::{collections::HashMap, path::{Path, PathBuf}};
 
-use mlua::{Error, Function, UserData, Value, Lua};
+use mlua::{AnyUserData, Error, FromLua, Function, IntoLua, Table, UserData, Value, Lua};
 use sdl2::{
-    event::Event, keyboard::{Keycode, Mod}, pixels::{
+    event::{Event, WindowEvent}, keyboard::{Keycode, Mod}, pixels::{
         Color,
         PixelFormat,
         PixelFormatEnum,
     }, rect::Rect,
 };
 
+use autocmd::Autocmds;
 use font::Fonts;
+use jobs::Jobs;
+use layout::{Direction, Layout};
+use register::{Register, Registers};
+use syntax::Syntaxes;
+use text::{RegionKind, Style};
+use theme::Theme;
 
-use crate::{keymap::Action, pane::{Mode, Pane}};
+const DIVIDER_WIDTH: u32 = 2;
+
+use crate::{cmdline::{CmdLine, CmdLineEvent, Command}, keymap::{Action, Motion}, pane::{Mode, Pane, SignColumn}, search::{Case, Search}};
 
 fn rgba(color: u32) -> Color {
     Color::from_u32(&PixelFormat::try_from(PixelFormatEnum::RGBA8888).unwrap(), color)
@@ -53,6 +77,7 @@ fn config_dir() -> PathBuf {
 struct Panes {
     panes: Vec<Pane>,
     current_pane: usize,
+    layout: Layout,
 }
 
 impl Panes {
@@ -60,14 +85,60 @@ impl Panes {
         Self{
             panes: Vec::new(),
             current_pane: 0,
+            layout: Layout::Leaf(0),
+        }
+    }
+    //adds the pane next to the current one and focuses it
+    fn split(&mut self, dir: Direction, pane: Pane) -> usize {
+        let new = self.panes.len();
+        self.panes.push(pane);
+        //splitting from a float splits the first tiled pane instead
+        if !self.layout.split(self.current_pane, new, dir) {
+            self.layout.split(self.layout.first(), new, dir);
         }
+        self.current_pane = new;
+        new
+    }
+    fn open_float(&mut self, pane: Pane, focus: bool) -> usize {
+        let new = self.panes.len();
+        self.panes.push(pane);
+        if focus {
+            self.current_pane = new;
+        }
+        new
+    }
+    fn close(&mut self, i: usize) -> Result<(), String> {
+        if i >= self.panes.len() {
+            return Err(format!("no pane {}", i+1));
+        }
+        if matches!(self.layout, Layout::Leaf(l) if l == i) {
+            return Err("can't close the last pane".to_string());
+        }
+        let pane = self.panes.remove(i);
+        let neighbour = self.layout.remove(i);
+        self.layout.renumber(i);
+        if self.current_pane == i {
+            //closing a tiled pane goes to the one that took its space, closing a popup goes back to the layout
+            self.current_pane = match neighbour {
+                Some(n) if pane.z_index == 0 => if n > i { n - 1 } else { n },
+                _ => self.layout.first(),
+            };
+        } else if self.current_pane > i {
+            self.current_pane -= 1;
+        }
+        Ok(())
     }
 }
 
 impl UserData for Panes {
     fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("count", |_, this| Ok(this.panes.len()));
+        fields.add_field_method_get("tiled", |_, this| Ok(this.panes.iter().filter(|p| p.z_index == 0).count()));
         fields.add_field_method_get("current_pane", |_, this| Ok(this.current_pane+1));
         fields.add_field_method_set("current_pane", |_, this, v: usize| {
+            if v == 0 || v > this.panes.len() {
+                return Err(Error::runtime(format!("no pane {v}, there are {}", this.panes.len())));
+            }
             this.current_pane = v-1;
             Ok(())
         });
@@ -84,6 +155,51 @@ impl UserData for Panes {
             })?;
             Ok(())
         });
+        methods.add_method_mut("split", |lua, this, (dir, path): (String, Option<String>)| {
+            let dir = match dir.as_str() {
+                "h" => Direction::Horizontal,
+                "v" => Direction::Vertical,
+                _ => return Err(Error::runtime(format!("invalid split direction {dir:?}, expected \"h\" or \"v\""))),
+            };
+            let mut pane = Pane::text(Rect::new(0, 0, 1, 1), 0, this.panes[this.current_pane].bg);
+            if let Some(path) = path {
+                let config = lua.globals().get::<AnyUserData>("config")?;
+                config.borrow_mut_scoped::<Config, _>(|config| config.open(&mut pane, PathBuf::from(path)))?.map_err(Error::external)?;
+            }
+            Ok(this.split(dir, pane)+1)
+        });
+        //x, y, w and h are in pixels and are the text area, the border and title go outside it
+        methods.add_method_mut("open_float", |lua, this, opts: Table| {
+            let z: u32 = opts.get::<Option<u32>>("z")?.unwrap_or(1);
+            if z == 0 {
+                return Err(Error::runtime("a float's z has to be above 0, use panes:split for tiled panes"));
+            }
+            let rect = Rect::new(opts.get("x")?, opts.get("y")?, opts.get::<u32>("w")?.max(1), opts.get::<u32>("h")?.max(1));
+            let bg = opts.get::<Option<u32>>("bg")?.map(rgba).or(this.panes[this.current_pane].bg);
+            let mut pane = Pane::text(rect, z, bg);
+            pane.border = opts.get::<Option<u32>>("border")?.map(rgba);
+            pane.title = opts.get("title")?;
+            if let Some(path) = opts.get::<Option<String>>("path")? {
+                let config = lua.globals().get::<AnyUserData>("config")?;
+                config.borrow_mut_scoped::<Config, _>(|config| config.open(&mut pane, PathBuf::from(path)))?.map_err(Error::external)?;
+            }
+            let focus = opts.get::<Option<bool>>("focus")?.unwrap_or(true);
+            Ok(this.open_float(pane, focus)+1)
+        });
+        methods.add_method_mut("close", |_, this, mut i: usize| {
+            if i == 0 {
+                i = this.current_pane+1;
+            }
+            this.close(i-1).map_err(Error::runtime)
+        });
+        //delta is the share of the split the pane grows by, so 0.1 is a tenth of it
+        methods.add_method_mut("resize", |_, this, (mut i, delta): (usize, f32)| {
+            if i == 0 {
+                i = this.current_pane+1;
+            }
+            this.layout.resize(i-1, delta);
+            Ok(())
+        });
 
     }
 }
@@ -95,8 +211,7 @@ struct Config {
 
     font_size: u16,
 
-    bg: Color,
-    text: Color,
+    theme: Theme,
 
     border: u32,
 
@@ -105,16 +220,54 @@ struct Config {
 
     command_timeout: u64,
 
+    scrolloff: usize,
+    sidescrolloff: usize,
+
+    //defaults for panes that don't set their own
+    number: bool,
+    relativenumber: bool,
+    signcolumn: SignColumn,
+
+    //soft wrap, long lines go on over more screen rows instead of scrolling sideways
+    wrap: bool,
+    //break at the last space that fits instead of in the middle of a word
+    linebreak: bool,
+    //continued rows keep the indent of the line they belong to
+    breakindent: bool,
+    //drawn at the start of continued rows
+    showbreak: String,
+
+    //called with each tiled pane and whether it's the current one, returns the left and right segments under it
+    statusline: Option<Function>,
+    //called with no arguments for the line above the layout, only drawn with showtabline on
+    tabline: Option<Function>,
+    showtabline: bool,
+
     leader: char,
 
     keymap: Keymaps,
+    registers: Registers,
+    last_search: Search,
+    syntaxes: Syntaxes,
 
     mode:Mode,
+
+    cmdline: CmdLine,
+    commands: HashMap<String, Command>,
+
+    quit: bool,
+    autocmds: Autocmds,
+    jobs: Jobs,
+    //set by config:reload, the main loop does it once the lua that asked has returned
+    reload: bool,
+    //reload when a lua file in the config dir changes
+    autoreload: bool,
 }
 
 impl Config {
     fn new(fonts: &mut Fonts) -> Self {
-        Self {
+        let (syntaxes, grammar_errors) = Syntaxes::new();
+        let mut config = Self {
             monospace: fonts.find_font_exists(&[
                            "DejaVu Sans Mono",
                            "Liberation Mono",
@@ -138,19 +291,79 @@ impl Config {
 
             font_size: 20,
 
-            bg: rgba(0x181818ff),
-            text: rgba(0xffffffff),
+            theme: Theme::new(),
 
             tabs: Some(4),
             tab_display: 4,
 
             command_timeout: 1000,
+
+            scrolloff: 3,
+            sidescrolloff: 5,
+
+            number: true,
+            relativenumber: false,
+            signcolumn: SignColumn::Auto,
+
+            wrap: false,
+            linebreak: true,
+            breakindent: true,
+            showbreak: "↪ ".to_string(),
+
+            statusline: None,
+            tabline: None,
+            showtabline: false,
+
             leader: ' ',
 
             keymap: Keymaps::new(),
+            registers: Registers::new(),
+            last_search: Search::new(),
+            syntaxes,
 
             mode: Mode::Normal,
+
+            cmdline: CmdLine::new(),
+            commands: HashMap::new(),
+
+            autocmds: Autocmds::new(),
+            jobs: Jobs::new(),
+            quit: false,
+            reload: false,
+            autoreload: false,
+        };
+        for e in grammar_errors {
+            config.cmdline.error(e);
+        }
+        config
+    }
+    //queues ModeChanged with the old and new mode chars, like n:i
+    fn set_mode(&mut self, mode: Mode) {
+        if mode != self.mode {
+            self.autocmds.queue.push((autocmd::Event::ModeChanged, format!("{}:{}", self.mode.to_char(), mode.to_char())));
+        }
+        self.mode = mode;
+    }
+    //opens `path` in `pane`, a grammar that won't load only turns highlighting off and the error is shown
+    fn open(&mut self, pane: &mut Pane, path: PathBuf) -> std::io::Result<()> {
+        if let Some(e) = pane.open(path, &mut self.syntaxes)? {
+            self.cmdline.error(e);
+        }
+        Ok(())
+    }
+    //back to the defaults for reloading, what was typed, yanked and searched for is kept
+    fn reset(&mut self, fonts: &mut Fonts) {
+        let old = std::mem::replace(self, Config::new(fonts));
+        self.registers = old.registers;
+        self.last_search = old.last_search;
+        //errors from building the new config, like a grammar that won't load, still get shown
+        let new = std::mem::replace(&mut self.cmdline, old.cmdline);
+        for e in new.messages {
+            self.cmdline.error(e);
         }
+        self.keymap.events = old.keymap.events;
+        self.jobs = old.jobs;
+        self.jobs.clear_timers();
     }
 }
 
@@ -176,14 +389,92 @@ impl UserData for Config {
             this.font_size = value;
             Ok(())
         });
-        fields.add_field_method_get("bg", |_, this| Ok(from_rgba(this.bg)));
-        fields.add_field_method_set("bg", |_, this, value: u32| {
-            this.bg = rgba(value);
+        //the colors from before highlight groups, each one is a color of a group
+        for (field, group, is_bg) in [("bg", "Normal", true), ("text", "Normal", false), ("visual", "Visual", true), ("search", "Search", true), ("divider", "Divider", true)] {
+            fields.add_field_method_get(field, move |_, this| Ok(from_rgba(if is_bg { this.theme.bg(group) } else { this.theme.fg(group) })));
+            fields.add_field_method_set(field, move |_, this, value: u32| {
+                if is_bg {
+                    this.theme.set_bg(group, rgba(value));
+                } else {
+                    this.theme.set_fg(group, rgba(value));
+                }
+                Ok(())
+            });
+        }
+        //what n and N look for, set by the / and ? prompts
+        fields.add_field_method_get("search_pattern", |_, this| Ok(this.last_search.pattern.clone()));
+        fields.add_field_method_set("search_pattern", |_, this, value: Option<String>| {
+            this.last_search.pattern = value;
+            this.last_search.hidden = false;
+            Ok(())
+        });
+        fields.add_field_method_get("search_backward", |_, this| Ok(this.last_search.backward));
+        fields.add_field_method_set("search_backward", |_, this, value: bool| {
+            this.last_search.backward = value;
+            Ok(())
+        });
+        fields.add_field_method_get("search_case", |_, this| Ok(match this.last_search.case {
+            Case::Smart => "smart",
+            Case::Ignore => "ignore",
+            Case::Match => "match",
+        }));
+        fields.add_field_method_set("search_case", |_, this, value: String| {
+            this.last_search.case = Case::from_name(&value).map_err(Error::runtime)?;
+            Ok(())
+        });
+        fields.add_field_method_get("hlsearch", |_, this| Ok(this.last_search.hlsearch));
+        fields.add_field_method_set("hlsearch", |_, this, value: bool| {
+            this.last_search.hlsearch = value;
+            Ok(())
+        });
+        fields.add_field_method_get("number", |_, this| Ok(this.number));
+        fields.add_field_method_set("number", |_, this, value: bool| {
+            this.number = value;
+            Ok(())
+        });
+        fields.add_field_method_get("relativenumber", |_, this| Ok(this.relativenumber));
+        fields.add_field_method_set("relativenumber", |_, this, value: bool| {
+            this.relativenumber = value;
+            Ok(())
+        });
+        fields.add_field_method_get("signcolumn", |_, this| Ok(this.signcolumn.name()));
+        fields.add_field_method_set("signcolumn", |_, this, value: SignColumn| {
+            this.signcolumn = value;
             Ok(())
         });
-        fields.add_field_method_get("text", |_, this| Ok(from_rgba(this.text)));
-        fields.add_field_method_set("text", |_, this, value: u32| {
-            this.text = rgba(value);
+        fields.add_field_method_get("wrap", |_, this| Ok(this.wrap));
+        fields.add_field_method_set("wrap", |_, this, value: bool| {
+            this.wrap = value;
+            Ok(())
+        });
+        fields.add_field_method_get("linebreak", |_, this| Ok(this.linebreak));
+        fields.add_field_method_set("linebreak", |_, this, value: bool| {
+            this.linebreak = value;
+            Ok(())
+        });
+        fields.add_field_method_get("breakindent", |_, this| Ok(this.breakindent));
+        fields.add_field_method_set("breakindent", |_, this, value: bool| {
+            this.breakindent = value;
+            Ok(())
+        });
+        fields.add_field_method_get("showbreak", |_, this| Ok(this.showbreak.clone()));
+        fields.add_field_method_set("showbreak", |_, this, value: String| {
+            this.showbreak = value;
+            Ok(())
+        });
+        fields.add_field_method_get("statusline", |_, this| Ok(this.statusline.clone()));
+        fields.add_field_method_set("statusline", |_, this, value: Option<Function>| {
+            this.statusline = value;
+            Ok(())
+        });
+        fields.add_field_method_get("tabline", |_, this| Ok(this.tabline.clone()));
+        fields.add_field_method_set("tabline", |_, this, value: Option<Function>| {
+            this.tabline = value;
+            Ok(())
+        });
+        fields.add_field_method_get("showtabline", |_, this| Ok(this.showtabline));
+        fields.add_field_method_set("showtabline", |_, this, value: bool| {
+            this.showtabline = value;
             Ok(())
         });
         fields.add_field_method_get("tabs", |_, this| Ok(this.tabs));
@@ -201,6 +492,16 @@ impl UserData for Config {
             this.command_timeout = value;
             Ok(())
         });
+        fields.add_field_method_get("scrolloff", |_, this| Ok(this.scrolloff));
+        fields.add_field_method_set("scrolloff", |_, this, value: usize| {
+            this.scrolloff = value;
+            Ok(())
+        });
+        fields.add_field_method_get("sidescrolloff", |_, this| Ok(this.sidescrolloff));
+        fields.add_field_method_set("sidescrolloff", |_, this, value: usize| {
+            this.sidescrolloff = value;
+            Ok(())
+        });
         fields.add_field_method_get("leader", |_, this| Ok(this.leader));
         fields.add_field_method_set("leader", |_, this, value: char| {
             this.leader = value;
@@ -208,9 +509,50 @@ impl UserData for Config {
         });
         fields.add_field_method_get("mode", |_, this| Ok(this.mode.to_char()));
         fields.add_field_method_set("mode", |_, this, value: char| {
-            this.mode = Mode::from(value);
+            let mode = Mode::try_from(value).map_err(Error::runtime)?;
+            if mode == Mode::Command && this.mode != Mode::Command {
+                this.cmdline.open(':', "");
+            }
+            if mode != Mode::Operator {
+                this.keymap.operator = None;
+            }
+            this.set_mode(mode);
+            Ok(())
+        });
+        //the register picked with "x for the next yank, delete or put
+        fields.add_field_method_get("register", |_, this| Ok(this.registers.selected.unwrap_or('"')));
+        fields.add_field_method_set("register", |_, this, value: Option<char>| {
+            this.registers.selected = value.map(Registers::check).transpose().map_err(Error::runtime)?;
             Ok(())
         });
+        //reads and writes go straight through to the registers, so config.registers.a = "text" works
+        fields.add_field_method_get("registers", |lua, _| {
+            let meta = lua.create_table()?;
+            meta.set("__index", lua.create_function(|lua, (_, c): (Table, char)| {
+                Registers::check(c).map_err(Error::runtime)?;
+                let config = lua.globals().get::<AnyUserData>("config")?;
+                config.borrow_scoped::<Config, _>(|config| config.registers.get(c))
+            })?)?;
+            meta.set("__newindex", lua.create_function(|lua, (_, c, reg): (Table, char, Option<Register>)| {
+                Registers::check(c).map_err(Error::runtime)?;
+                let config = lua.globals().get::<AnyUserData>("config")?;
+                config.borrow_mut_scoped::<Config, _>(|config| {
+                    config.registers.set(c, reg.unwrap_or(Register { text: String::new(), kind: RegionKind::Char }));
+                })
+            })?)?;
+            let registers = lua.create_table()?;
+            registers.set_metatable(Some(meta))?;
+            Ok(registers)
+        });
+        fields.add_field_method_get("autoreload", |_, this| Ok(this.autoreload));
+        fields.add_field_method_set("autoreload", |_, this, value: bool| {
+            this.autoreload = value;
+            Ok(())
+        });
+        fields.add_field_method_get("messages", |_, this| Ok(this.cmdline.messages.clone()));
+        fields.add_field_method_get("recording", |_, this| Ok(this.keymap.recording()));
+        //the count and keys of a sequence still being typed, like 2d or "a
+        fields.add_field_method_get("pending", |_, this| Ok(this.keymap.pending()));
         fields.add_field_method_get("count", |_, this| Ok(this.keymap.count.parse::<usize>().unwrap_or(1)));
         fields.add_field_method_set("count", |_, this, value: usize| {
             this.keymap.count = value.to_string();
@@ -236,13 +578,13 @@ impl UserData for Config {
             };
             match keys {
                 Value::String(s) => {
-                    this.keymap.set(mode, s.to_string_lossy(), func, this.leader);
+                    this.keymap.set(mode, s.to_string_lossy(), func, this.leader).map_err(Error::runtime)?;
                 }
                 Value::Table(s) => {
                     let len = s.len()?;
                     for i in 1..=len {
                         let k:String = s.get(i)?; 
-                        this.keymap.set(mode.clone(), k, func.clone(), this.leader);
+                        this.keymap.set(mode.clone(), k, func.clone(), this.leader).map_err(Error::runtime)?;
                     }
                 }
                 _ => return Err(Error::FromLuaConversionError {
@@ -253,13 +595,160 @@ impl UserData for Config {
             }
             return Ok(());
         });
+        //operators get the pane and the region their motion covers, typing one twice like dd runs it on lines
+        methods.add_method_mut("operator", |_, this, (keys, func): (String, Function)| {
+            this.keymap.set("nvo".to_string(), keys, Action::Operator(func), this.leader).map_err(Error::runtime)
+        });
+        //motions get the pane, the count or nil and the char for motions like f, and return x, y or a region
+        //opts: linewise, inclusive, char, and object for text objects that only make sense after an operator or in visual mode
+        methods.add_method_mut("motion", |_, this, (keys, func, opts): (String, Function, Option<Table>)| {
+            let get = |name: &str| -> mlua::Result<bool> {
+                Ok(match &opts {
+                    Some(opts) => opts.get::<Option<bool>>(name)?.unwrap_or(false),
+                    None => false,
+                })
+            };
+            let motion = Motion {
+                func,
+                linewise: get("linewise")?,
+                inclusive: get("inclusive")?,
+                char: get("char")?,
+            };
+            let modes = if get("object")? { "vo" } else { "nvo" };
+            this.keymap.set(modes.to_string(), keys, Action::Motion(motion), this.leader).map_err(Error::runtime)
+        });
+        //the next typed char is passed to the function, or nil if it was escape
+        methods.add_method_mut("getchar", |_, this, func: Function| {
+            this.keymap.char_callback = Some(func);
+            Ok(())
+        });
+        //starts recording a macro into the register, without one it stops and stores what was typed
+        methods.add_method_mut("record", |_, this, reg: Option<char>| {
+            match reg {
+                Some(c) => {
+                    Registers::check(c).map_err(Error::runtime)?;
+                    this.keymap.start_recording(c);
+                }
+                None => {
+                    if let Some((c, text)) = this.keymap.stop_recording() {
+                        this.registers.set(c, Register { text, kind: RegionKind::Char });
+                    }
+                }
+            }
+            Ok(())
+        });
+        //plays the macro in the register count times, @ plays the last one again
+        methods.add_method_mut("play", |_, this, (reg, count): (char, Option<usize>)| {
+            let reg = if reg == '@' {
+                this.keymap.last_played.ok_or_else(|| Error::runtime("no macro has been played yet"))?
+            } else {
+                Registers::check(reg).map_err(Error::runtime)?
+            };
+            let Some(macro_) = this.registers.get(reg) else {
+                return Err(Error::runtime(format!("nothing in register {reg}")));
+            };
+            this.keymap.last_played = Some(reg);
+            this.keymap.call_macro(macro_.text.repeat(count.unwrap_or(1).max(1))).map_err(Error::runtime)
+        });
+        //registers a tree-sitter grammar for a filetype, opts are
+        //library, a shared library to load the language from, with symbol defaulting to tree_sitter_<name>
+        //query, the highlights query, and extensions, the file extensions that get the filetype
+        //without a library only the query and extensions of a grammar already there change
+        methods.add_method_mut("grammar", |_, this, (name, opts): (String, Table)| {
+            let library: Option<String> = opts.get("library")?;
+            let symbol = opts.get::<Option<String>>("symbol")?.unwrap_or_else(|| format!("tree_sitter_{name}"));
+            let library = library.as_ref().map(|l| (Path::new(l), symbol.as_str()));
+            let extensions = opts.get::<Option<Vec<String>>>("extensions")?.unwrap_or_default();
+            this.syntaxes.register(&name, library, opts.get("query")?, &extensions).map_err(Error::runtime)
+        });
+        //sets a highlight group to a style or to {link = "Group"}, without a style it gives the group's style back
+        //tree-sitter captures use groups like "@keyword" or fall back to "Keyword"
+        methods.add_method_mut("hl", |lua, this, (name, style): (String, Option<Table>)| {
+            let Some(style) = style else {
+                return this.theme.get(&name).into_lua(lua);
+            };
+            match style.get::<Option<String>>("link")? {
+                Some(to) => this.theme.link(&name, &to),
+                None => this.theme.set(&name, Style::from_lua(Value::Table(style), lua)?),
+            }
+            Ok(Value::Nil)
+        });
+        //hides the search highlight until the next search
+        methods.add_method_mut("nohlsearch", |_, this, ()| {
+            this.last_search.hidden = true;
+            Ok(())
+        });
+        methods.add_method_mut("repeat_last", |_, this, ()| {
+            this.keymap.repeat_last();
+            Ok(())
+        });
         methods.add_method_mut("cmd", |_, this, s: String| {
-            this.keymap.call_macro(s);
+            this.keymap.call_macro(s).map_err(Error::runtime)
+        });
+        methods.add_method_mut("command", |_, this, (name, func, opts): (String, Function, Option<Table>)| {
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(Error::runtime(format!("invalid command name {name:?}")));
+            }
+            this.commands.insert(name, Command::new(func, opts)?);
+            Ok(())
+        });
+        methods.add_method_mut("cmdline", |_, this, (prompt, text): (Option<char>, Option<String>)| {
+            this.cmdline.open(prompt.unwrap_or(':'), &text.unwrap_or_default());
+            this.set_mode(Mode::Command);
+            Ok(())
+        });
+        //loads config_dir()/themes/<name> as .lua, base16 .yaml or .yml, or vs code .json
+        //a function rather than a method so a lua theme can use config while it runs
+        methods.add_function("colorscheme", |lua, (this, name): (AnyUserData, String)| {
+            let dir = config_dir().join("themes");
+            let Some(path) = ["lua", "yaml", "yml", "json"].iter().map(|ext| dir.join(format!("{name}.{ext}"))).find(|p| p.exists()) else {
+                return Err(Error::runtime(format!("no theme {name} in {}", dir.display())));
+            };
+            if path.extension().is_some_and(|e| e == "lua") {
+                return lua.load(path).exec();
+            }
+            this.borrow_mut_scoped::<Config, _>(|config| config.theme.load(&path))?.map_err(Error::runtime)
+        });
+        methods.add_method_mut("quit", |_, this, ()| {
+            this.quit = true;
+            Ok(())
+        });
+        //events is a name or several separated by commas, pattern is a glob or nil for all, returns an id for config:off
+        methods.add_method_mut("on", |_, this, (events, pattern, func): (String, Option<String>, Function)| {
+            this.autocmds.add(&events, pattern.as_deref(), func).map_err(Error::runtime)
+        });
+        methods.add_method_mut("off", |_, this, id: usize| Ok(this.autocmds.remove(id)));
+        //ms from now, returns an id for config:cancel
+        methods.add_method_mut("defer", |_, this, (ms, func): (u64, Function)| Ok(this.jobs.add_timer(ms, false, func)));
+        methods.add_method_mut("every", |_, this, (ms, func): (u64, Function)| Ok(this.jobs.add_timer(ms.max(1), true, func)));
+        methods.add_method_mut("spawn", |_, this, opts: Table| this.jobs.spawn(opts));
+        methods.add_method_mut("cancel", |_, this, id: usize| Ok(this.jobs.cancel(id)));
+        //runs default.lua and init.lua again from scratch, the open panes stay as they are
+        methods.add_method_mut("reload", |_, this, ()| {
+            this.reload = true;
             Ok(())
         });
     }
 }
 
+//runs a line typed after :
+fn execute(line: &str, lua: &Lua, config: &mut Config, panes: &mut Panes) -> mlua::Result<()> {
+    let parsed = cmdline::parse(line).map_err(Error::runtime)?;
+    let pane = &mut panes.panes[panes.current_pane];
+    let range = parsed.resolve(pane).map_err(Error::runtime)?;
+    if parsed.name.is_empty() {
+        //a range on its own jumps to that line
+        if !parsed.range.is_empty() {
+            pane.set_cursor(0, range.1 as u32 - 1);
+        }
+        return Ok(());
+    }
+    let cmd = cmdline::find(&config.commands, &parsed.name).map_err(Error::runtime)?;
+    let args = cmdline::command_args(lua, cmd, &parsed, range)?;
+    let func = cmd.func.clone();
+    func.call::<()>(args)
+}
+
 pub fn main() {
     let lua = Lua::new();
 
@@ -268,6 +757,7 @@ pub fn main() {
 
     let mut fonts = Fonts::new();
     let mut config = Config::new(&mut fonts);
+    config.registers.clipboard = Some(video_subsystem.clipboard());
     let mut panes = Panes::new();
 
 
@@ -277,7 +767,7 @@ pub fn main() {
         .unwrap();
 
 
-    lua.scope(|scope| {
+    let result = lua.scope(|scope| {
         unsafe{
             let c = &mut config as *mut Config;
             let ud = scope.create_userdata_ref_mut(&mut *c)?;
@@ -293,33 +783,142 @@ pub fn main() {
                 "#,
                 config_dir().to_str().unwrap()
         )).exec()?;
-        lua.load(include_str!("./default.lua")).exec()?;
-        let init_file = config_dir().join("init.lua");
-        if init_file.exists() {
-            lua.load(&std::fs::read_to_string(&init_file)?).exec()?;
+        //set up before init.lua runs, so jobs it starts can wake the main loop too
+        let events = sdl_context.event().unwrap();
+        let reload_event = unsafe { events.register_event() }.unwrap();
+        let jobs_event = unsafe { events.register_event() }.unwrap();
+        config.jobs.set_waker(events.event_sender(), jobs_event);
+        reload::load(&lua, &mut config, &mut fonts)?;
+        //every file given gets its own pane, side by side
+        for path in std::env::args().skip(1) {
+            let mut pane = Pane::text(Rect::new(0, 0, 1, 1), 0, None);
+            if let Err(e) = config.open(&mut pane, PathBuf::from(&path)) {
+                config.cmdline.error(format!("couldn't open {path}: {e}"));
+                continue;
+            }
+            if panes.panes.is_empty() {
+                panes.panes.push(pane);
+            } else {
+                panes.split(Direction::Vertical, pane);
+            }
         }
-        panes.panes.push(Pane::text(Rect::new(0, 0, 1, 1), 0, config.bg));
+        if panes.panes.is_empty() {
+            panes.panes.push(Pane::text(Rect::new(0, 0, 1, 1), 0, None));
+        }
+        panes.current_pane = 0;
         let mut canvas = window.into_canvas().build().unwrap();
 
         let mut event_pump = sdl_context.event_pump().unwrap();
+        let mut watcher = None;
 
+        //only redraw after something happened, so an idle editor doesn't spin
+        let mut dirty = true;
         'running: loop {
-            canvas.set_draw_color(config.bg);
-            canvas.clear();
+            if config.autoreload != watcher.is_some() {
+                watcher = None;
+                if config.autoreload {
+                    match reload::watch(events.event_sender(), reload_event) {
+                        Ok(w) => watcher = Some(w),
+                        Err(e) => {
+                            config.cmdline.error(format!("can't watch {}: {e}", config_dir().display()));
+                            config.autoreload = false;
+                        }
+                    }
+                }
+            }
+            if dirty {
+                let font = (fonts.find_font(&[&config.monospace]), config.font_size);
+                let line_height = fonts.load_font(&font).height() as u32;
+                canvas.set_draw_color(config.theme.bg("Normal"));
+                canvas.clear();
+                for pane in panes.panes.iter_mut() {
+                    pane.update_syntax();
+                }
+
+                //the layout is worked out again every frame, so resizing the window just works
+                let (w, h) = canvas.window().size();
+                let mut area = Rect::new(config.border as i32, config.border as i32, w - config.border*2, (h - config.border*2).saturating_sub(line_height).max(1));
+                if config.showtabline && let Some(tabline) = &config.tabline && area.height() > line_height {
+                    let rect = Rect::new(area.x, area.y, area.width(), line_height);
+                    statusline::Line::call(tabline, ()).render(&mut canvas, &mut fonts, &config, rect, "TabLineFill");
+                    area = Rect::new(area.x, area.y + line_height as i32, area.width(), area.height() - line_height);
+                }
+                let mut rects = Vec::new();
+                let mut dividers = Vec::new();
+                panes.layout.rects(area, DIVIDER_WIDTH, &mut rects, &mut dividers);
+                for (i, mut rect) in rects {
+                    //the status line takes the bottom line of the pane's space, unless that's all there is
+                    let status = config.statusline.as_ref().filter(|_| rect.height() > line_height);
+                    if status.is_some() {
+                        rect.set_height(rect.height() - line_height);
+                    }
+                    let pane = &mut panes.panes[i];
+                    pane.position(rect.x, rect.y, rect.width(), rect.height());
+                    pane.fix_cursor(&config, &mut fonts);
+                    pane.render(&mut canvas, &mut fonts, &config);
+                    if let Some(func) = status {
+                        let current = i == panes.current_pane;
+                        let line = lua.scope(|scope| {
+                            let pane = scope.create_userdata_ref(&panes.panes[i])?;
+                            Ok(statusline::Line::call(func, (pane, current)))
+                        }).unwrap_or_else(|e| {
+                            config.cmdline.error(e);
+                            statusline::Line { left: Vec::new(), right: Vec::new() }
+                        });
+                        let rect = Rect::new(rect.x, rect.bottom(), rect.width(), line_height);
+                        line.render(&mut canvas, &mut fonts, &config, rect, if current { "StatusLine" } else { "StatusLineNC" });
+                    }
+                }
+                canvas.set_clip_rect(None);
+                canvas.set_draw_color(config.theme.bg("Divider"));
+                for divider in dividers {
+                    canvas.fill_rect(divider).unwrap();
+                }
+
+                //floats go over the layout, lowest z first
+                let mut floats: Vec<usize> = (0..panes.panes.len()).filter(|&i| panes.panes[i].z_index != 0).collect();
+                floats.sort_by_key(|&i| panes.panes[i].z_index);
+                for i in floats {
+                    let pane = &mut panes.panes[i];
+                    pane.fix_cursor(&config, &mut fonts);
+                    pane.render_frame(&mut canvas, &mut fonts, &config, line_height);
+                    pane.render(&mut canvas, &mut fonts, &config);
+                }
+                canvas.set_clip_rect(None);
 
-            for pane in panes.panes.iter_mut() {
+                //the last line of the window is for the command line and messages
                 let (w, h) = canvas.window().size();
-                pane.position(config.border as i32, config.border as i32, w - config.border*2, h - config.border*2);
-                pane.fix_cursor(&config, &mut fonts);
-                pane.render(&mut canvas, &mut fonts, &config);
+                let rect = Rect::new(config.border as i32, (h - config.border) as i32 - line_height as i32, w - config.border*2, line_height);
+                config.cmdline.render(&mut canvas, &mut fonts, &config, rect, config.mode == Mode::Command);
+                canvas.present();
+                dirty = false;
             }
 
-            let pane = panes.panes.get_mut(panes.current_pane).unwrap();
-            for event in event_pump.poll_iter() {
+            //block until there is input, queued keys, pending key sequences, timers and job output wake up early
+            let wait = [config.keymap.timeout_in(config.command_timeout), config.jobs.timeout()].into_iter().flatten().min();
+            let event = if !config.keymap.events.is_empty() {
+                event_pump.poll_event()
+            } else if let Some(wait) = wait {
+                event_pump.wait_event_timeout(wait.as_millis() as u32)
+            } else {
+                Some(event_pump.wait_event())
+            };
+            for event in event.into_iter().chain(event_pump.poll_iter()) {
+                dirty = true;
                 match event {
                     Event::Quit {..} => {
                         break 'running
                     },
+                    Event::User { type_, .. } if type_ == reload_event => config.reload = true,
+                    Event::Window { win_event, .. } => {
+                        let event = match win_event {
+                            WindowEvent::SizeChanged(..) => autocmd::Event::WindowResized,
+                            WindowEvent::FocusGained => autocmd::Event::FocusGained,
+                            WindowEvent::FocusLost => autocmd::Event::FocusLost,
+                            _ => continue,
+                        };
+                        config.autocmds.queue.push((event, String::new()));
+                    }
                     Event::KeyDown {keycode: Some(keycode), keymod,..} => {
                         config.keymap.events.push((keycode, keymod, None, false));
                     },
@@ -333,12 +932,101 @@ pub fn main() {
                     _ => {}
                 }
             }
-            if let Some((keycode, keymod, text, finish)) = first(&mut config.keymap.events) {
-                pane.handle_events(&mut config, &mut fonts, keycode, keymod, text, finish);
+            if jobs::run(&mut config) {
+                dirty = true;
+            }
+            if let Some((event, typed)) = config.keymap.next_event() {
+                let (keycode, keymod, text, finish) = event.clone();
+                //keys answering :s///c aren't repeated with . either
+                let confirming = panes.panes.iter().position(|p| p.confirm.is_some());
+                let mode = if confirming.is_some() { Mode::Command } else { config.mode.clone() };
+                if let Some(i) = confirming {
+                    config.cmdline.message = Some(panes.panes[i].confirm_key(keycode, text.as_deref()));
+                } else if config.mode == Mode::Command && Search::is_prompt(config.cmdline.prompt) {
+                    let prompt = config.cmdline.prompt;
+                    let event = config.cmdline.handle(keycode, keymod, text.clone(), &config.commands);
+                    match event {
+                        CmdLineEvent::Unhandled => config.keymap.handle(Mode::Command, keycode, keymod, text, finish),
+                        _ => {
+                            if !matches!(event, CmdLineEvent::Edited) {
+                                config.set_mode(Mode::Normal);
+                            }
+                            let pane = panes.panes.get_mut(panes.current_pane).unwrap();
+                            let pattern = config.cmdline.text();
+                            if let Some(e) = config.last_search.prompt_event(prompt, &event, &pattern, pane) {
+                                config.cmdline.error(e);
+                            }
+                        }
+                    }
+                } else if config.mode == Mode::Command {
+                    match config.cmdline.handle(keycode, keymod, text.clone(), &config.commands) {
+                        CmdLineEvent::Edited => {}
+                        CmdLineEvent::Cancel => config.set_mode(Mode::Normal),
+                        CmdLineEvent::Submit(line) => {
+                            config.set_mode(Mode::Normal);
+                            if let Err(e) = execute(&line, &lua, &mut config, &mut panes) {
+                                config.cmdline.error(e);
+                            }
+                        }
+                        CmdLineEvent::Unhandled => {
+                            config.keymap.handle(Mode::Command, keycode, keymod, text, finish);
+                        }
+                    }
+                } else {
+                    let pane = panes.panes.get_mut(panes.current_pane).unwrap();
+                    pane.handle_events(&lua, &mut config, keycode, keymod, text, finish);
+                }
+                config.keymap.record(event, &mode, typed);
+                dirty = true;
+            }
+            if config.quit {
+                break 'running;
+            }
+            if config.reload {
+                config.reset(&mut fonts);
+                if let Err(e) = reload::flush_modules(&lua, &config_dir()).and_then(|_| reload::load(&lua, &mut config, &mut fonts)) {
+                    config.cmdline.error(e);
+                }
+                //grammars registered by the old config are gone, so each pane looks its filetype up again
+                for pane in panes.panes.iter_mut() {
+                    let filetype = pane.filetype().map(str::to_string);
+                    if let Err(e) = pane.set_filetype(filetype, &mut config.syntaxes) {
+                        config.cmdline.error(e);
+                    }
+                }
+                dirty = true;
+            }
+            if config.keymap.handle_timeout(config.mode.clone(), config.command_timeout) {
+                dirty = true;
+            }
+            if let Some(ready) = config.keymap.ready.take() && let Err(e) = operator::run(ready, &lua, &mut config, &mut panes) {
+                config.cmdline.error(e);
+            }
+            for e in config.keymap.errors.drain(..) {
+                config.cmdline.error(e);
+            }
+            if autocmd::dispatch(&lua, &mut config, &mut panes) {
+                dirty = true;
+            }
+            let changed = panes.panes.get(panes.current_pane).is_some_and(|p| p.has_changes());
+            config.keymap.end_change(&config.mode, changed);
+            //each action is its own undo step, but a whole insert session is one and so is a whole :s///c
+            if config.mode != Mode::Insert {
+                for pane in panes.panes.iter_mut().filter(|p| p.confirm.is_none()) {
+                    pane.commit_undo();
+                }
             }
-            config.keymap.handle_timeout(config.mode.clone(), config.command_timeout);
-            canvas.present();
+        }
+        //nothing is left to show an error in, so it goes to stderr
+        let handlers = config.autocmds.handlers(autocmd::Event::VimLeave, "");
+        if let Err(e) = autocmd::run(&lua, handlers, autocmd::Event::VimLeave, "", None, |_| Ok(())) {
+            eprintln!("{e}");
         }
         Ok(())
-    }).unwrap();
+    });
+    //only default.lua failing gets here, init.lua errors are shown in the editor
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,105 @@
+//runs the operators and motions the keymap resolved, operators get the region a motion covers
+use mlua::{FromLua, Function, Lua, MultiValue, Value};
+
+use crate::{keymap::{Motion, Ready}, pane::{Mode, Pane}, text::{Region, RegionKind}, Config, Panes};
+
+enum Target {
+    Pos(usize, usize),
+    Object(Region),
+}
+
+//motions are called with the pane, the count or nil and the char for motions like f
+//they return x, y or a region, or nothing if they couldn't move
+fn call_motion(lua: &Lua, pane: &mut Pane, motion: &Motion, count: Option<usize>, c: Option<char>) -> mlua::Result<Option<Target>> {
+    let ret = lua.scope(|scope| {
+        let arg = scope.create_userdata_ref_mut(pane)?;
+        motion.func.call::<MultiValue>((arg, count, c.map(String::from)))
+    })?;
+    let mut ret = ret.into_iter();
+    match ret.next() {
+        None | Some(Value::Nil) => Ok(None),
+        Some(Value::Table(t)) => Ok(Some(Target::Object(Region::from_lua(Value::Table(t), lua)?))),
+        Some(x) => {
+            let x = i64::from_lua(x, lua)?.max(0) as usize;
+            let y = i64::from_lua(ret.next().unwrap_or(Value::Nil), lua)?.max(0) as usize;
+            Ok(Some(Target::Pos(x, y)))
+        }
+    }
+}
+
+fn call_operator(lua: &Lua, pane: &mut Pane, op: &Function, region: Region) -> mlua::Result<()> {
+    lua.scope(|scope| {
+        let arg = scope.create_userdata_ref_mut(pane)?;
+        op.call::<()>((arg, region))
+    })
+}
+
+//counts multiply, so 2d3w deletes six words
+fn total(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(1) * b.unwrap_or(1)),
+    }
+}
+
+pub fn run(ready: Ready, lua: &Lua, config: &mut Config, panes: &mut Panes) -> mlua::Result<()> {
+    let pane = &mut panes.panes[panes.current_pane];
+    match ready {
+        Ready::Cancel => {
+            config.keymap.operator = None;
+            if config.mode == Mode::Operator {
+                config.set_mode(Mode::Normal);
+            }
+        }
+        Ready::Operator(op, count) => {
+            config.keymap.operator = Some((op, count));
+            config.set_mode(Mode::Operator);
+        }
+        Ready::Visual(op) => {
+            let Some(region) = pane.selection() else {
+                return Ok(());
+            };
+            config.set_mode(Mode::Normal);
+            call_operator(lua, pane, &op, region)?;
+        }
+        Ready::Lines(count) => {
+            let Some((op, n)) = config.keymap.operator.take() else {
+                return Ok(());
+            };
+            config.set_mode(Mode::Normal);
+            let (_, y) = pane.get_cursor();
+            let y = y as usize;
+            let last = (y + total(count, n).unwrap_or(1) - 1).min(pane.line_count() - 1);
+            let region = Region { start: (0, y), end: (0, last), kind: RegionKind::Line };
+            call_operator(lua, pane, &op, region)?;
+        }
+        Ready::Motion(motion, count, c) => {
+            if config.mode == Mode::Operator {
+                let Some((op, n)) = config.keymap.operator.take() else {
+                    config.set_mode(Mode::Normal);
+                    return Ok(());
+                };
+                config.set_mode(Mode::Normal);
+                let region = match call_motion(lua, pane, &motion, total(count, n), c)? {
+                    None => None,
+                    Some(Target::Object(r)) => Some(r),
+                    Some(Target::Pos(x, y)) => pane.motion_region(x, y, motion.linewise, motion.inclusive),
+                };
+                if let Some(region) = region {
+                    call_operator(lua, pane, &op, region)?;
+                }
+                return Ok(());
+            }
+            match call_motion(lua, pane, &motion, count, c)? {
+                None => {}
+                Some(Target::Pos(x, y)) => pane.set_cursor(x as u32, y as u32),
+                Some(Target::Object(r)) if config.mode == Mode::Visual => pane.set_selection(Some(r)),
+                Some(Target::Object(r)) => {
+                    let r = r.normalized();
+                    pane.set_cursor(r.start.0 as u32, r.start.1 as u32);
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,20 +1,21 @@
-use mlua::UserData;
-use sdl2::{keyboard::{Keycode, Mod}, pixels::{Color, PixelFormatEnum}, rect::Rect, render::{BlendMode, RenderTarget, Texture, TextureCreator}, surface::Surface, ttf::FontStyle, video::WindowContext};
+use std::{collections::HashMap, io::{self, Write}, ops::Range, path::PathBuf, time::Duration};
 
-type Canvas = sdl2::render::Canvas<sdl2::video::Window>;
+use mlua::{AnyUserData, Error, FromLua, Lua, Table, UserData, Value};
+use regex::Regex;
+use sdl2::{keyboard::{Keycode, Mod}, pixels::Color, rect::{Point, Rect}, ttf::FontStyle};
 
-use crate::{font::{Fonts, Font}, Config};
+type Canvas = sdl2::render::Canvas<sdl2::video::Window>;
 
-#[derive(Debug)]
-pub struct Line {
-    pub cells: Vec<TextCell>,
-}
+use crate::{autocmd::{self, Event}, font::{Fonts, Font}, register::{Register, Registers}, search::{self, Case, Search}, substitute::{self, Confirm, Flags}, syntax::Syntaxes, text::{Region, RegionKind, Span, Style, Text}, undo::UndoTree, wrap::{self, Row}, Config};
 
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
 pub enum Mode {
     Normal,
     Insert,
     Visual,
+    Command,
+    //after an operator like d, waiting for the motion it works on
+    Operator,
 }
 
 impl Mode {
@@ -23,46 +24,128 @@ impl Mode {
             Self::Insert => 'i',
             Self::Normal => 'n',
             Self::Visual => 'v',
+            Self::Command => 'c',
+            Self::Operator => 'o',
         }
     }
 }
 
-impl From<char> for Mode {
-    fn from(c: char) -> Self {
+impl TryFrom<char> for Mode {
+    type Error = String;
+    fn try_from(c: char) -> Result<Self, String> {
         match c.to_lowercase().collect::<Vec<char>>()[0] {
-            'i' => Self::Insert,
-            'n' => Self::Normal,
-            'v' => Self::Visual,
-            _ => panic!("mode doesn't exist"),
+            'i' => Ok(Self::Insert),
+            'n' => Ok(Self::Normal),
+            'v' => Ok(Self::Visual),
+            'c' => Ok(Self::Command),
+            'o' => Ok(Self::Operator),
+            _ => Err(format!("mode {c} doesn't exist")),
+        }
+    }
+}
+
+//what gets drawn over the text on screen, worked out once a frame
+struct Highlights {
+    //earlier entries win where they overlap
+    syntax: Vec<(Range<usize>, Style)>,
+    matches: Vec<Range<usize>>,
+}
+
+//draws line `l` of the pane, styling each char from the span layer
+//columns before `left_col` are scrolled out of view, a wrapping pane never scrolls sideways but can start the top line a few rows in
+fn render_line(pane: &Pane, canvas: &mut Canvas, y: &mut i32, fonts: &mut Fonts, l: usize, config: &Config, highlights: &Highlights) {
+    let BufType::Text{buf: text, cursor, ..} = &pane.buf;
+    let left = pane.left_col;
+    let selection = pane.selection();
+    let font = (fonts.find_font(&[&config.monospace]), config.font_size);
+    let line_height = fonts.load_font(&font).height() as u32;
+    let char_width = fonts.char_size(&font, FontStyle::NORMAL, ' ').0 as i32;
+    let line = text.line(l);
+    let start = text.line_to_char(l);
+    let spans: Vec<&Span> = text.spans_in(start..start + line.len_chars()).collect();
+    let end = start + line.len_chars();
+    let syntax: Vec<&(Range<usize>, Style)> = highlights.syntax.iter().filter(|(r, _)| r.start < end && r.end > start).collect();
+    let rows = pane.line_rows(l, config);
+    let skip = if l == pane.top_line { pane.top_row } else { 0 };
+    let text_x = pane.rect.x + pane.gutter_width as i32;
+    let mut row = 0;
+    let mut height = 0;
+    let mut x = text_x;
+    let mut col = 0;
+    //the chars, then None for the spot past the end where the cursor can be
+    for (c, char) in line.chars().map(Some).chain(std::iter::once(None)).enumerate() {
+        if rows.get(row + 1).is_some_and(|r| r.start == c) {
+            if row >= skip {
+                *y += if height == 0 { line_height } else { height } as i32;
+            }
+            row += 1;
+            height = 0;
+            x = text_x + rows[row].indent as i32 * char_width;
+            if row >= skip {
+                let mut bx = x - config.showbreak.chars().count() as i32 * char_width;
+                for b in config.showbreak.chars() {
+                    fonts.draw_char(canvas, &font, FontStyle::NORMAL, b, config.theme.fg("NonText"), Point::new(bx, *y));
+                    bx += char_width;
+                }
+            }
+        }
+        if row < skip {
+            continue;
+        }
+        let Some(char) = char else {
+            if cursor.y as usize == l && cursor.x as usize >= line.len_chars() && col >= left {
+                //the cursor is past the end of the line, so draw it on a blank space
+                let ch = TextCell::new(' ', &Style::default(), font.clone(), config);
+                let (w, h) = ch.size(fonts, config);
+                let mut inver = false;
+                cursor.cursor_type.render(Rect::new(x, *y, w, h), canvas, config.theme.get("Cursor").bg.unwrap_or(ch.fg), &mut inver);
+                let mut ex = x;
+                ch.render(canvas, *y, &mut ex, &mut height, fonts, inver, config);
+            }
+            break;
+        };
+        col += char_cols(char, config);
+        if col <= left {
+            continue;
+        }
+        let mut style = syntax.iter().find(|(r, _)| r.contains(&(start + c))).map_or(Style::default(), |(_, s)| *s);
+        for span in spans.iter().filter(|s| s.start <= start + c && start + c < s.end) {
+            style.merge(&span.style);
         }
+        if highlights.matches.iter().any(|m| m.contains(&(start + c))) {
+            style.merge(&config.theme.get("Search"));
+        }
+        if selection.is_some_and(|s| s.contains(c, l)) {
+            style.merge(&config.theme.get("Visual"));
+        }
+        let ch = TextCell::new(char, &style, font.clone(), config);
+        let mut inver = false;
+        if cursor.y as usize == l && cursor.x as usize == c {
+            let (w, h) = ch.size(fonts, config);
+            cursor.cursor_type.render(Rect::new(x, *y, w, h), canvas, config.theme.get("Cursor").bg.unwrap_or(ch.fg), &mut inver);
+        }
+        ch.render(canvas, *y, &mut x, &mut height, fonts, inver, config);
+    }
+    if row >= skip {
+        *y += if height == 0 { line_height } else { height } as i32;
     }
 }
 
-impl Line {
-    pub fn render(&self, canvas: &mut Canvas, y: &mut i32, fonts: &mut Fonts, cursor: &Cursor, l: usize, sx: i32, config:&Config) {
-        let mut height = 0;
-        let mut x = sx;
-        let mut c = 0;
-        if self.cells.len() == 0 {
-            let f = fonts.find_font(&[&config.monospace]);
-            let font = fonts.load_font(&(f, config.font_size));
-            *y+=font.height() as i32; 
-            return; 
-        }
-        for ch in self.cells.iter() {
-            let ox = x;
-            let mut inver = false;
-            if cursor.y as usize == l && cursor.x as usize == c {
-                let (w, h) = ch.size(fonts);
-                cursor.cursor_type.render(ox, w as u32, *y, h, canvas, ch, &mut inver);
-            }
-            ch.render(canvas, *y, &mut x, &mut height, fonts, inver, config);
-            c+=1;
-        }
-        *y += height as i32;
+//how many columns a char takes up on screen
+pub fn char_cols(c: char, config: &Config) -> usize {
+    if c == '\t' {
+        config.tab_display
+    } else {
+        1
     }
 }
 
+//the screen column `x` chars into the line starts at
+fn display_col(text: &Text, x: usize, y: usize, config: &Config) -> usize {
+    text.line(y).chars().take(x).map(|c| char_cols(c, config)).sum()
+}
+
+//a char resolved for drawing, these only exist while rendering
 #[derive(Debug)]
 pub struct TextCell {
     pub char: char, 
@@ -73,51 +156,46 @@ pub struct TextCell {
 }
 
 impl TextCell {
-    pub fn size(&self, fonts: &mut Fonts) -> (u32, u32) {
-        let font = fonts.load_font(&self.font);
-        font.set_style(self.font_style);
-        return font.size_of_char(self.char).unwrap();
+    pub fn new(char: char, style: &Style, font: Font, config: &Config) -> Self {
+        Self {
+            char,
+            fg: style.fg.unwrap_or(config.theme.fg("Normal")),
+            bg: style.bg,
+            font,
+            font_style: style.font_style,
+        }
+    }
+    pub fn size(&self, fonts: &mut Fonts, config: &Config) -> (u32, u32) {
+        if self.char == '\t' {
+            let (w, h) = fonts.char_size(&self.font, self.font_style, ' ');
+            return (w*config.tab_display as u32, h);
+        }
+        fonts.char_size(&self.font, self.font_style, self.char)
     }
     pub fn render(&self, canvas: &mut Canvas, y: i32, x: &mut i32, height: &mut u32, fonts: &mut Fonts, inver: bool, config: &Config) {
-        let font = fonts.load_font(&self.font);
-        font.set_style(self.font_style);
-        let (w, h)= if self.char == '\t' {
-            let (w, h) = font.size_of_char(' ').unwrap();
-            (w*config.tab_display as u32, h)
-        } else {
-            font.size_of_char(self.char).unwrap()
-        };
+        let (w, h) = self.size(fonts, config);
         if h > *height  {
             *height = h;
         }
 
-        let tc = canvas.texture_creator();
-        let mut s = None;
-        if self.char != '\t' {
-            if inver {
-                s = Some(font.render_char(self.char).blended(self.bg.unwrap_or(config.bg)).unwrap());
-            } else {
-                s = Some(font.render_char(self.char).blended(self.fg).unwrap()); 
-            }
-        }
         let rect = Rect::new(*x, y, w, h);
-        let mut tex = None;
-        if let Some(s) = s {
-            tex = Some(tc.create_texture_from_surface(&s).unwrap());
-        }
-
         if !inver {
             if let Some(bg) = self.bg {
                 canvas.set_draw_color(bg);
                 canvas.fill_rect(rect).unwrap();
             }
         } else {
-            canvas.set_draw_color(self.fg);
+            canvas.set_draw_color(config.theme.get("Cursor").bg.unwrap_or(self.fg));
             canvas.fill_rect(rect).unwrap();
         }
 
-        if let Some(tex) = tex {
-            canvas.copy(&tex, None, rect).unwrap();
+        if self.char != '\t' {
+            let color = if inver {
+                config.theme.get("Cursor").fg.or(self.bg).unwrap_or(config.theme.bg("Normal"))
+            } else {
+                self.fg
+            };
+            fonts.draw_char(canvas, &self.font, self.font_style, self.char, color, Point::new(*x, y));
         }
 
         *x+=w as i32;
@@ -132,8 +210,10 @@ pub enum CursorType {
 }
 
 impl CursorType {
-    fn render(&self, x:i32, w:u32, y:i32, h:u32, canvas: &mut Canvas, ch: &TextCell, inver: &mut bool) {
-        canvas.set_draw_color(ch.fg);
+    //`cell` is where the character under the cursor goes
+    fn render(&self, cell: Rect, canvas: &mut Canvas, color: Color, inver: &mut bool) {
+        let (x, y, w, h) = (cell.x, cell.y, cell.width(), cell.height());
+        canvas.set_draw_color(color);
         match self {
             Self::Block => {
                 *inver = true;
@@ -158,62 +238,317 @@ pub struct Cursor {
     pub x: u32,
     pub y: u32,
     pub cursor_type: CursorType,
+    //the other end of the selection, the cursor itself is the end that moves
+    pub anchor: Option<(u32, u32)>,
+    pub selection_kind: RegionKind,
 }
 
-pub struct TextBufOptions {
+//how far :earlier and :later go, a number of undo states or a time like "5m"
+pub enum TimeTravel {
+    Steps(usize),
+    Time(Duration),
+}
 
+impl FromLua for TimeTravel {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+        match value {
+            Value::Integer(n) => Ok(Self::Steps(n.max(0) as usize)),
+            Value::Number(n) => Ok(Self::Steps(n.max(0.0) as usize)),
+            Value::String(s) => {
+                let s = s.to_str()?;
+                let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+                let (n, unit) = s.split_at(split);
+                let n: u64 = n.parse().map_err(|_| Error::runtime(format!("invalid time {s}")))?;
+                let secs = match unit {
+                    "" => return Ok(Self::Steps(n as usize)),
+                    "s" => n,
+                    "m" => n * 60,
+                    "h" => n * 60 * 60,
+                    "d" => n * 60 * 60 * 24,
+                    _ => return Err(Error::runtime(format!("invalid time unit {unit}"))),
+                };
+                Ok(Self::Time(Duration::from_secs(secs)))
+            }
+            _ => Err(Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "TimeTravel".to_string(),
+                message: Some("expected a count or a time like \"5m\"".to_string()),
+            }),
+        }
+    }
+}
+
+//whether a pane has room for signs, auto only makes room once the buffer has some
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SignColumn {
+    Auto,
+    Yes,
+    No,
+}
+
+impl SignColumn {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Yes => "yes",
+            Self::No => "no",
+        }
+    }
+}
+
+impl FromLua for SignColumn {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+        match value {
+            Value::Boolean(true) => Ok(Self::Yes),
+            Value::Boolean(false) => Ok(Self::No),
+            Value::String(s) => match s.to_str()?.as_ref() {
+                "auto" => Ok(Self::Auto),
+                "yes" => Ok(Self::Yes),
+                "no" => Ok(Self::No),
+                other => Err(Error::runtime(format!("invalid signcolumn {other:?}, expected \"auto\", \"yes\" or \"no\""))),
+            },
+            _ => Err(Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "SignColumn".to_string(),
+                message: Some("expected \"auto\", \"yes\", \"no\" or a boolean".to_string()),
+            }),
+        }
+    }
+}
+
+//how the file was written, so saving puts it back the same way
+pub struct TextBufOptions {
+    //every line ended in \r\n, the buffer only has \n and they're put back on save
+    crlf: bool,
+    //the file ended in a line break, the buffer doesn't have it as an empty last line
+    eol: bool,
 }
 impl TextBufOptions {
     fn new() -> Self {
         Self {
-
+            crlf: false,
+            eol: true,
         }
     }
 }
 
 pub enum BufType {
-    Text{buf: Vec<Line>, cursor: Cursor, opts: TextBufOptions}
+    Text{
+        buf: Text,
+        cursor: Cursor,
+        opts: TextBufOptions,
+        path: Option<PathBuf>,
+        modified: bool,
+        history: UndoTree,
+        marks: HashMap<char, (u32, u32)>,
+        //picks the grammar the buffer is highlighted with
+        filetype: Option<String>,
+    }
 }
 
-//TASK(20260111-161006-254-n6-036): actually implement floating panes
 pub struct Pane {
     pub rect: Rect, 
     pub z_index: u32, //if z-index is 0, then its a tiled widnow, otherwise it floats
     pub buf: BufType,
-    pub bg: Color,
+    //falls back to the Normal group
+    pub bg: Option<Color>,
+    //only floating panes get a frame, drawn around rect
+    pub border: Option<Color>,
+    pub title: Option<String>,
+    pub top_line: usize,
+    //how many screen rows of a wrapped top_line are scrolled off
+    pub top_row: usize,
+    pub left_col: usize,
+    //how many lines and columns fit in the pane, updated by fix_cursor
+    pub rows: usize,
+    pub cols: usize,
+    //how wide the sign and line number columns are in pixels, also updated by fix_cursor
+    pub gutter_width: u32,
+    //these fall back to the config options of the same name when they're nil
+    pub number: Option<bool>,
+    pub relativenumber: Option<bool>,
+    pub signcolumn: Option<SignColumn>,
+    pub wrap: Option<bool>,
+    //set while :s///c waits for an answer, keys go to it instead of the keymap
+    pub confirm: Option<Confirm>,
+    //events that happened to the pane since the main loop last ran their handlers
+    pub fired: Vec<Event>,
+    //the text's tick when TextChanged was last fired
+    seen_tick: usize,
+    //and the cursor when CursorMoved was, anything can move it so it's compared rather than told
+    seen_cursor: (u32, u32),
 }
 
-//TASK(20260111-161148-304-n6-294): make tiled panes automatically change width height and position
-
 impl Pane {
-    pub fn text(rect: Rect, z_index: u32, bg: Color) -> Self {
+    pub fn text(rect: Rect, z_index: u32, bg: Option<Color>) -> Self {
         Self {
             rect,
             z_index,
             bg,
+            border: None,
+            title: None,
+            top_line: 0,
+            top_row: 0,
+            left_col: 0,
+            rows: 1,
+            cols: 1,
+            gutter_width: 0,
+            number: None,
+            relativenumber: None,
+            signcolumn: None,
+            wrap: None,
+            confirm: None,
+            fired: Vec::new(),
+            seen_tick: 0,
+            seen_cursor: (0, 0),
             buf: BufType::Text{
-                buf: Vec::new(),
+                buf: Text::new(),
                 cursor: Cursor {
                     x: 0,
                     y: 0,
-                    cursor_type: CursorType::Block
+                    cursor_type: CursorType::Block,
+                    anchor: None,
+                    selection_kind: RegionKind::Char,
                 },
-                opts: TextBufOptions::new()
+                opts: TextBufOptions::new(),
+                path: None,
+                modified: false,
+                history: UndoTree::new(),
+                marks: HashMap::new(),
+                filetype: None,
             },
         }
     }
+    //loads the file into the buffer, a file that doesn't exist yet gives an empty buffer,
+    //the file is open even when its grammar won't load, that error is given back to show
+    pub fn open(&mut self, path: PathBuf, syntaxes: &mut Syntaxes) -> io::Result<Option<String>> {
+        match &mut self.buf {
+            BufType::Text{buf, cursor, opts, path: p, modified, history, marks, filetype} => {
+                let text = match std::fs::read_to_string(&path) {
+                    Ok(text) => text,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                    Err(e) => return Err(e),
+                };
+                //mixed line endings are left alone, the odd \r stays in the text
+                let lines = text.matches('\n').count();
+                opts.crlf = lines > 0 && text.matches("\r\n").count() == lines;
+                let text = if opts.crlf { text.replace("\r\n", "\n") } else { text };
+                //a new or empty file gets a line break at the end once there's something in it
+                opts.eol = text.is_empty() || text.ends_with('\n');
+                *buf = Text::from_str(text.strip_suffix('\n').unwrap_or(&text));
+                *filetype = syntaxes.detect(&path);
+                let (grammar, error) = match filetype.as_deref().map(|f| syntaxes.get(f)) {
+                    Some(Ok(grammar)) => (grammar, None),
+                    Some(Err(e)) => (None, Some(e)),
+                    None => (None, None),
+                };
+                buf.set_syntax(grammar);
+                cursor.x = 0;
+                cursor.y = 0;
+                *p = Some(path);
+                *modified = false;
+                *history = UndoTree::new();
+                marks.clear();
+                self.seen_tick = 0;
+                self.seen_cursor = (0, 0);
+                self.fired.push(Event::BufRead);
+                Ok(error)
+            }
+        }
+    }
+    //writes the buffer to `path`, or to the path it was opened from
+    pub fn save(&mut self, path: Option<PathBuf>) -> io::Result<()> {
+        match &mut self.buf {
+            BufType::Text{buf, opts, path: p, modified, history, ..} => {
+                if let Some(path) = path {
+                    *p = Some(path);
+                }
+                let Some(path) = p else {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "no file name"));
+                };
+                let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+                let newline: &[u8] = if opts.crlf { b"\r\n" } else { b"\n" };
+                for chunk in buf.rope().chunks() {
+                    let mut lines = chunk.split('\n');
+                    file.write_all(lines.next().unwrap_or("").as_bytes())?;
+                    for line in lines {
+                        file.write_all(newline)?;
+                        file.write_all(line.as_bytes())?;
+                    }
+                }
+                if opts.eol && buf.rope().len_chars() > 0 {
+                    file.write_all(newline)?;
+                }
+                file.flush()?;
+                history.commit(buf.take_journal());
+                history.mark_saved();
+                *modified = false;
+                self.fired.push(Event::BufWritePost);
+                Ok(())
+            }
+        }
+    }
+    pub fn path(&self) -> Option<&PathBuf> {
+        match &self.buf {
+            BufType::Text{path, ..} => path.as_ref(),
+        }
+    }
+    pub fn filetype(&self) -> Option<&str> {
+        match &self.buf {
+            BufType::Text{filetype, ..} => filetype.as_deref(),
+        }
+    }
+    //the filetype is set even when its grammar won't load, highlighting is just off
+    pub fn set_filetype(&mut self, name: Option<String>, syntaxes: &mut Syntaxes) -> Result<(), String> {
+        match &mut self.buf {
+            BufType::Text{buf, filetype, ..} => {
+                let grammar = name.as_deref().map(|f| syntaxes.get(f)).transpose();
+                *filetype = name;
+                buf.set_syntax(grammar?.flatten());
+                Ok(())
+            }
+        }
+    }
+    //reparses what changed since the last frame
+    pub fn update_syntax(&mut self) {
+        match &mut self.buf {
+            BufType::Text{buf, ..} => buf.update_syntax(),
+        }
+    }
+    pub fn modified(&self) -> bool {
+        match &self.buf {
+            BufType::Text{modified, ..} => *modified,
+        }
+    }
     pub fn render(&self, canvas: &mut Canvas, fonts: &mut Fonts, config: &Config) {
         match &self.buf {
-            BufType::Text{buf, cursor, ..} => {
+            BufType::Text{buf, ..} => {
                 canvas.set_clip_rect(self.rect);
-                canvas.set_draw_color(self.bg);
+                canvas.set_draw_color(self.bg.unwrap_or(config.theme.bg("Normal")));
                 canvas.fill_rect(self.rect).unwrap();
 
+                //only the text on screen is looked at
+                let top = self.top_line.min(buf.len_lines() - 1);
+                let bottom = top + self.rows + 1;
+                let end = if bottom < buf.len_lines() { buf.line_to_char(bottom) } else { buf.len_chars() };
+                let visible = buf.line_to_char(top)..end;
+                //while :s///c asks only its match shows
+                let typing = (config.mode == Mode::Command && Search::is_prompt(config.cmdline.prompt)).then(|| config.cmdline.text());
+                let matches = match config.last_search.highlight(typing) {
+                    _ if let Some(confirm) = &self.confirm => confirm.current().filter(|_| !confirm.stale(buf)).into_iter().collect(),
+                    Some(re) => buf.find(&re, visible.clone()),
+                    None => Vec::new(),
+                };
+                let highlights = Highlights { syntax: buf.highlights(visible, &config.theme), matches };
+
                 let mut y = self.rect.y;
-                let mut i = 0;
-                for line in buf.iter() {
-                    line.render(canvas, &mut y, fonts, cursor, i, self.rect.y, config);
-                    i+=1;
+                for l in self.top_line..buf.len_lines() {
+                    if y >= self.rect.bottom() {
+                        break;
+                    }
+                    let line_y = y;
+                    render_line(self, canvas, &mut y, fonts, l, config, &highlights);
+                    self.render_gutter(canvas, fonts, config, l, line_y, (y - line_y) as u32);
                 }
             }
             _ => {
@@ -221,15 +556,98 @@ impl Pane {
             }
         }
     }
+    //how many columns the signs and the line numbers take up
+    fn gutter_cols(&self, config: &Config) -> (usize, usize) {
+        let BufType::Text{buf, ..} = &self.buf;
+        let signs = match self.signcolumn.unwrap_or(config.signcolumn) {
+            SignColumn::Yes => 2,
+            SignColumn::Auto if buf.has_signs() => 2,
+            _ => 0,
+        };
+        let numbers = if self.number.unwrap_or(config.number) || self.relativenumber.unwrap_or(config.relativenumber) {
+            //room for the widest number and a space after it
+            buf.len_lines().to_string().len().max(3) + 1
+        } else {
+            0
+        };
+        (signs, numbers)
+    }
+    //draws the sign and the line number of line `l`, whose text is at `y` and `h` tall
+    fn render_gutter(&self, canvas: &mut Canvas, fonts: &mut Fonts, config: &Config, l: usize, y: i32, h: u32) {
+        if self.gutter_width == 0 {
+            return;
+        }
+        let BufType::Text{buf, cursor, ..} = &self.buf;
+        let (signs, numbers) = self.gutter_cols(config);
+        let font = (fonts.find_font(&[&config.monospace]), config.font_size);
+        let char_width = fonts.char_size(&font, FontStyle::NORMAL, ' ').0.max(1) as i32;
+        let mut draw = |text: &str, group: &str, x: i32, canvas: &mut Canvas| {
+            let style = config.theme.get(group);
+            if let Some(bg) = style.bg {
+                canvas.set_draw_color(bg);
+                canvas.fill_rect(Rect::new(x, y, text.chars().count() as u32 * char_width as u32, h.max(1))).unwrap();
+            }
+            let fg = style.fg.unwrap_or(config.theme.fg("Normal"));
+            for (i, c) in text.chars().enumerate() {
+                fonts.draw_char(canvas, &font, style.font_style, c, fg, Point::new(x + i as i32 * char_width, y));
+            }
+        };
+        let x = self.rect.x;
+        if signs > 0 {
+            match buf.sign(l) {
+                Some(sign) => {
+                    let text: String = sign.text.chars().chain(std::iter::repeat(' ')).take(signs).collect();
+                    draw(&text, &sign.hl, x, canvas);
+                }
+                None => draw(&" ".repeat(signs), "SignColumn", x, canvas),
+            }
+        }
+        if numbers > 0 {
+            let width = numbers - 1;
+            let current = cursor.y as usize == l;
+            let number = self.number.unwrap_or(config.number);
+            let text = if self.relativenumber.unwrap_or(config.relativenumber) {
+                //with both on the cursor line shows its own number, on the left like vim
+                match (current, number) {
+                    (true, true) => format!("{:<width$} ", l + 1),
+                    (true, false) => format!("{:>width$} ", 0),
+                    _ => format!("{:>width$} ", l.abs_diff(cursor.y as usize)),
+                }
+            } else {
+                format!("{:>width$} ", l + 1)
+            };
+            draw(&text, if current { "CursorLineNr" } else { "LineNr" }, x + signs as i32 * char_width, canvas);
+        }
+    }
+    //a position past the text lands on the nearest one in it
     pub fn set_cursor(&mut self, x: u32, y: u32) {
         match &mut self.buf {
-            BufType::Text{cursor, ..} => {
-                cursor.x = x;
-                cursor.y = y;
+            BufType::Text{buf, cursor, ..} => {
+                let (x, y) = buf.clamp_pos(x as usize, y as usize);
+                cursor.x = x as u32;
+                cursor.y = y as u32;
             }
             _ => panic!("pane not text buffer")
         }
     }
+    //what event patterns are matched against, the path or nothing for a buffer without one
+    pub fn event_match(&self) -> String {
+        self.path().map_or(String::new(), |p| p.to_string_lossy().to_string())
+    }
+    //whether the text changed since the last time this was asked
+    pub fn take_text_changed(&mut self) -> bool {
+        let BufType::Text{buf, ..} = &self.buf;
+        let changed = buf.tick() != self.seen_tick;
+        self.seen_tick = buf.tick();
+        changed
+    }
+    //whether the cursor moved since the last time this was asked
+    pub fn take_cursor_moved(&mut self) -> bool {
+        let cursor = self.get_cursor();
+        let moved = cursor != self.seen_cursor;
+        self.seen_cursor = cursor;
+        moved
+    }
     pub fn get_cursor(&self) -> (u32, u32) {
         match &self.buf {
             BufType::Text{cursor, ..} => {
@@ -241,42 +659,569 @@ impl Pane {
             _ => panic!("pane not text buffer")
         }
     }
-    pub fn fix_cursor(&mut self, config: &Config, font: &mut Fonts) {
+    pub fn fix_cursor(&mut self, config: &Config, fonts: &mut Fonts) {
+        let font = (fonts.find_font(&[&config.monospace]), config.font_size);
+        let line_height = fonts.load_font(&font).height().max(1) as u32;
+        let char_width = fonts.char_size(&font, FontStyle::NORMAL, ' ').0.max(1);
+        let (signs, numbers) = self.gutter_cols(config);
+        self.gutter_width = ((signs + numbers) as u32 * char_width).min(self.rect.width().saturating_sub(char_width));
+        self.rows = (self.rect.height() / line_height).max(1) as usize;
+        self.cols = ((self.rect.width() - self.gutter_width) / char_width).max(1) as usize;
         match &mut self.buf {
             BufType::Text{buf, cursor, ..} => {
-                while buf.len() <= cursor.y as usize {
-                    buf.push(Line {
-                        cells: Vec::new()
-                    })
-                }
-                while buf[cursor.y as usize].cells.len() <= cursor.x as usize {
-                    buf[cursor.y as usize].cells.push(
-                        TextCell { 
-                            char: ' ', 
-                            fg: config.text,
-                            bg: None,
-                            font: (font.find_font(&[&config.monospace.clone()]), config.font_size),
-                            font_style: FontStyle::NORMAL,
+                if cursor.y as usize >= buf.len_lines() {
+                    cursor.y = buf.len_lines() as u32 - 1;
+                }
+                let len = buf.line_len(cursor.y as usize) as u32;
+                if cursor.x > len {
+                    cursor.x = len;
+                }
+            }
+        }
+        self.scroll_rows(config);
+        let wraps = self.wraps(config);
+        let BufType::Text{buf, cursor, marks, ..} = &mut self.buf;
+        let col = display_col(buf, cursor.x as usize, cursor.y as usize, config);
+        let so = config.sidescrolloff.min((self.cols - 1) / 2);
+        if wraps {
+            self.left_col = 0;
+        } else if col < self.left_col + so {
+            self.left_col = col.saturating_sub(so);
+        } else if col + so >= self.left_col + self.cols {
+            self.left_col = col + so + 1 - self.cols;
+        }
+        //visual mode always has a selection and the other modes never do
+        match config.mode {
+            Mode::Visual => {
+                if cursor.anchor.is_none() {
+                    cursor.anchor = Some((cursor.x, cursor.y));
+                    cursor.selection_kind = RegionKind::Char;
+                }
+            }
+            _ => {
+                //remember the selection as '< and '> once it's left
+                if let Some((x, y)) = cursor.anchor.take() {
+                    let r = Region {
+                        start: (x as usize, y as usize),
+                        end: (cursor.x as usize, cursor.y as usize),
+                        kind: cursor.selection_kind,
+                    }.normalized();
+                    marks.insert('<', (r.start.0 as u32, r.start.1 as u32));
+                    marks.insert('>', (r.end.0 as u32, r.end.1 as u32));
+                }
+            }
+        }
+        cursor.cursor_type = match config.mode {
+            Mode::Normal=> CursorType::Block,
+            Mode::Insert=> CursorType::Line,
+            Mode::Visual=> CursorType::Block,
+            Mode::Command=> CursorType::Block,
+            Mode::Operator=> CursorType::Underline,
+        }
+    }
+    pub fn wraps(&self, config: &Config) -> bool {
+        self.wrap.unwrap_or(config.wrap)
+    }
+    //the screen rows line `l` is drawn on, a pane that doesn't wrap draws every line on one
+    pub fn line_rows(&self, l: usize, config: &Config) -> Vec<Row> {
+        let BufType::Text{buf, cursor, ..} = &self.buf;
+        let width = if self.wraps(config) { self.cols } else { usize::MAX };
+        wrap::rows(buf.line(l), width, cursor.y as usize == l, config)
+    }
+    //the screen row of line `y` that char `x` is on and the column it's at
+    pub fn screen_pos(&self, x: usize, y: usize, config: &Config) -> (usize, usize) {
+        let BufType::Text{buf, ..} = &self.buf;
+        wrap::locate(&self.line_rows(y, config), buf.line(y), x, config)
+    }
+    //the char of line `y` drawn at column `col` of its screen row `row`
+    pub fn screen_char(&self, y: usize, row: usize, col: usize, config: &Config) -> usize {
+        let BufType::Text{buf, ..} = &self.buf;
+        wrap::char_at(&self.line_rows(y, config), buf.line(y), row, col, config)
+    }
+    //the screen row `n` rows above row `r` of line `l`, or the very first one
+    fn rows_back(&self, (mut l, mut r): (usize, usize), mut n: usize, config: &Config) -> (usize, usize) {
+        while n > r {
+            if l == 0 {
+                return (0, 0);
+            }
+            n -= r + 1;
+            l -= 1;
+            r = self.line_rows(l, config).len() - 1;
+        }
+        (l, r - n)
+    }
+    //scrolls so the cursor stays at least scrolloff screen rows away from the edges
+    fn scroll_rows(&mut self, config: &Config) {
+        let (x, y) = self.get_cursor();
+        let (x, y) = (x as usize, y as usize);
+        let row = self.screen_pos(x, y, config).0;
+        let so = config.scrolloff.min((self.rows - 1) / 2);
+        self.top_line = self.top_line.min(self.line_count() - 1);
+        self.top_row = self.top_row.min(self.line_rows(self.top_line, config).len() - 1);
+        let up = self.rows_back((y, row), so, config);
+        if up < (self.top_line, self.top_row) {
+            (self.top_line, self.top_row) = up;
+            return;
+        }
+        //count the rows down to the cursor, stopping once it's clearly below the view
+        let room = self.rows - 1 - so;
+        let (mut l, mut r, mut n) = (self.top_line, self.top_row, 0);
+        while l < y && n <= room {
+            n += self.line_rows(l, config).len() - r;
+            l += 1;
+            r = 0;
+        }
+        if l < y || n + row - r > room {
+            (self.top_line, self.top_row) = self.rows_back((y, row), room, config);
+        }
+    }
+    //scrolls the view, dragging the cursor along when it would leave the screen like <C-e> in vim
+    pub fn scroll(&mut self, dx: isize, dy: isize, config: &Config) {
+        match &mut self.buf {
+            BufType::Text{buf, cursor, ..} => {
+                let last = buf.len_lines() - 1;
+                self.top_line = self.top_line.saturating_add_signed(dy).min(last);
+                if dy != 0 {
+                    self.top_row = 0;
+                }
+                self.left_col = self.left_col.saturating_add_signed(dx);
+
+                let so = config.scrolloff.min((self.rows - 1) / 2);
+                let y = cursor.y as usize;
+                if y < self.top_line + so {
+                    cursor.y = (self.top_line + so).min(last) as u32;
+                } else if y + so >= self.top_line + self.rows {
+                    cursor.y = (self.top_line + self.rows).saturating_sub(so + 1) as u32;
+                }
+                let so = config.sidescrolloff.min((self.cols - 1) / 2);
+                let y = cursor.y as usize;
+                let line = buf.line(y);
+                let col = display_col(buf, cursor.x as usize, y, config);
+                let target = if col < self.left_col + so {
+                    Some(self.left_col + so)
+                } else if col + so >= self.left_col + self.cols {
+                    Some((self.left_col + self.cols).saturating_sub(so + 1))
+                } else {
+                    None
+                };
+                if let Some(target) = target {
+                    //walk the line to find the char at that screen column
+                    let mut col = 0;
+                    let mut x = 0;
+                    for c in line.chars() {
+                        let next = col + char_cols(c, config);
+                        if next > target {
+                            break;
                         }
-                    )
+                        col = next;
+                        x += 1;
+                    }
+                    cursor.x = x;
+                }
+            }
+        }
+    }
+    pub fn line_count(&self) -> usize {
+        match &self.buf {
+            BufType::Text{buf, ..} => buf.len_lines(),
+        }
+    }
+    pub fn line_len(&self, y: usize) -> usize {
+        match &self.buf {
+            BufType::Text{buf, ..} => buf.line_len(y),
+        }
+    }
+    pub fn mark(&self, name: char) -> Option<(u32, u32)> {
+        match &self.buf {
+            BufType::Text{marks, ..} => marks.get(&name).copied(),
+        }
+    }
+    pub fn selection(&self) -> Option<Region> {
+        match &self.buf {
+            BufType::Text{cursor, ..} => {
+                let (x, y) = cursor.anchor?;
+                Some(Region {
+                    start: (x as usize, y as usize),
+                    end: (cursor.x as usize, cursor.y as usize),
+                    kind: cursor.selection_kind,
+                })
+            }
+        }
+    }
+    //the start of the region becomes the anchor and the cursor moves to its end
+    pub fn set_selection(&mut self, region: Option<Region>) {
+        match &mut self.buf {
+            BufType::Text{buf, cursor, ..} => {
+                let Some(region) = region else {
+                    cursor.anchor = None;
+                    return;
+                };
+                let (ax, ay) = buf.clamp_pos(region.start.0, region.start.1);
+                let (x, y) = buf.clamp_pos(region.end.0, region.end.1);
+                cursor.anchor = Some((ax as u32, ay as u32));
+                cursor.x = x as u32;
+                cursor.y = y as u32;
+                cursor.selection_kind = region.kind;
+            }
+        }
+    }
+    pub fn get_text(&self, region: &Region) -> String {
+        match &self.buf {
+            BufType::Text{buf, ..} => buf.region_text(region),
+        }
+    }
+    //removes the region and returns what was in it, whole lines are taken out for line regions
+    pub fn delete(&mut self, region: &Region) -> String {
+        match &mut self.buf {
+            BufType::Text{buf, cursor, modified, ..} => {
+                let r = region.normalized();
+                let text = buf.region_text(&r);
+                let mut ranges = buf.region_ranges(&r);
+                if r.kind == RegionKind::Line && ranges[0].end == buf.len_chars() && ranges[0].start > 0 {
+                    //the last line has no line break of its own, so take the one before it
+                    ranges[0].start -= 1;
                 }
-                cursor.cursor_type = match config.mode {
-                    Mode::Normal=> CursorType::Block,
-                    Mode::Insert=> CursorType::Line,
-                    Mode::Visual=> CursorType::Block,
+                for range in ranges.into_iter().rev() {
+                    buf.remove(range);
                 }
+                *modified = true;
+                match r.kind {
+                    RegionKind::Line => {
+                        cursor.x = 0;
+                        cursor.y = r.start.1.min(buf.len_lines() - 1) as u32;
+                    }
+                    _ => {
+                        cursor.x = r.start.0 as u32;
+                        cursor.y = r.start.1 as u32;
+                    }
+                }
+                text
+            }
+        }
+    }
+    //where the count'th match of `re` after the cursor starts, or before it going backward, wrapping around the buffer
+    pub fn search(&self, re: &Regex, backward: bool, count: usize) -> Option<(usize, usize)> {
+        match &self.buf {
+            BufType::Text{buf, cursor, ..} => {
+                let mut at = buf.pos_to_char(cursor.x as usize, cursor.y as usize);
+                for _ in 0..count.max(1) {
+                    at = buf.find_next(re, at, backward)?.start;
+                }
+                Some(buf.char_to_pos(at))
+            }
+        }
+    }
+    //replaces the matches of `re` on lines `first..=last`, returns how many there were
+    //with confirm nothing changes yet, the matches wait for confirm_key
+    pub fn substitute(&mut self, re: &Regex, lines: (usize, usize), rep: &str, flags: &Flags) -> usize {
+        match &mut self.buf {
+            BufType::Text{buf, cursor, modified, ..} => {
+                let found = substitute::matches(buf, re, lines, rep, flags.global);
+                let n = found.len();
+                if flags.count || n == 0 {
+                    return n;
+                }
+                if flags.confirm {
+                    let (x, y) = buf.char_to_pos(found[0].range.start);
+                    cursor.x = x as u32;
+                    cursor.y = y as u32;
+                    self.confirm = Some(Confirm::new(found, buf));
+                    return n;
+                }
+                //going backwards keeps the ranges of the matches before each one right
+                for r in found.iter().rev() {
+                    substitute::apply(buf, r, 0);
+                }
+                //the cursor ends up on the last line that changed
+                let last = &found[n - 1];
+                let before: isize = found[..n - 1].iter().map(|r| r.text.chars().count() as isize - r.range.len() as isize).sum();
+                cursor.x = 0;
+                cursor.y = buf.char_to_pos(last.range.start.saturating_add_signed(before)).1 as u32;
+                *modified = true;
+                n
             }
-            _=>{}
         }
     }
-    pub fn insert_char(&mut self, c: char, config: &Config, font: &mut Fonts) {
+    //answers the :s///c prompt, returns what the command line should say next
+    pub fn confirm_key(&mut self, key: Keycode, text: Option<&str>) -> String {
+        let Some(confirm) = &mut self.confirm else {
+            return String::new();
+        };
         match &mut self.buf {
+            BufType::Text{buf, cursor, modified, ..} => {
+                let answer = match key {
+                    Keycode::Escape => 'q',
+                    _ => text.and_then(|t| t.chars().next()).unwrap_or('\0'),
+                };
+                if confirm.stale(buf) {
+                    let report = format!("the text changed, stopped after {}", substitute::report(confirm.done));
+                    self.confirm = None;
+                    return report;
+                }
+                let done = confirm.done;
+                match answer {
+                    'y' => confirm.replace(buf, 1),
+                    'l' => {
+                        confirm.replace(buf, 1);
+                        confirm.quit();
+                    }
+                    'a' => confirm.replace(buf, usize::MAX),
+                    'n' => confirm.skip(),
+                    'q' => confirm.quit(),
+                    _ => {}
+                }
+                if confirm.done > done {
+                    *modified = true;
+                }
+                if let Some(range) = confirm.current() {
+                    let (x, y) = buf.char_to_pos(range.start);
+                    cursor.x = x as u32;
+                    cursor.y = y as u32;
+                    return confirm.prompt();
+                }
+                let report = substitute::report(confirm.done);
+                self.confirm = None;
+                report
+            }
+        }
+    }
+    //the region between the cursor and where a motion landed, None if an exclusive motion didn't move
+    pub fn motion_region(&self, x: usize, y: usize, linewise: bool, inclusive: bool) -> Option<Region> {
+        match &self.buf {
             BufType::Text{buf, cursor, ..} => {
+                let y = y.min(buf.len_lines() - 1);
+                let target = (x.min(buf.line_len(y)), y);
+                let here = (cursor.x as usize, cursor.y as usize);
+                if linewise {
+                    return Some(Region { start: (0, here.1), end: (0, target.1), kind: RegionKind::Line }.normalized());
+                }
+                let (start, mut end) = if (target.1, target.0) < (here.1, here.0) { (target, here) } else { (here, target) };
+                if !inclusive {
+                    if start == end {
+                        return None;
+                    }
+                    let mut at = buf.pos_to_char(end.0, end.1) - 1;
+                    //landing at the start of a line leaves the line break alone, like dw on the last word of a line
+                    if end.0 == 0 && at > buf.pos_to_char(start.0, start.1) {
+                        at -= 1;
+                    }
+                    end = buf.char_to_pos(at);
+                }
+                Some(Region { start, end, kind: RegionKind::Char })
+            }
+        }
+    }
+    //puts the register after the cursor, or before it, lines go below or above the cursor line
+    pub fn put(&mut self, reg: &Register, before: bool, count: usize) {
+        match &mut self.buf {
+            BufType::Text{buf, cursor, modified, ..} => {
+                let (x, y) = (cursor.x as usize, cursor.y as usize);
+                *modified = true;
+                match reg.kind {
+                    RegionKind::Char => {
+                        let text = reg.text.repeat(count);
+                        let after = if !before && buf.line_len(y) > 0 { 1 } else { 0 };
+                        let idx = buf.pos_to_char(x + after, y);
+                        buf.insert(idx, &text);
+                        let (x, y) = buf.char_to_pos(idx + text.chars().count().saturating_sub(1));
+                        cursor.x = x as u32;
+                        cursor.y = y as u32;
+                    }
+                    RegionKind::Line => {
+                        let mut line = reg.text.clone();
+                        if !line.ends_with('\n') {
+                            line.push('\n');
+                        }
+                        let mut text = line.repeat(count);
+                        let target = if before { y } else { y + 1 };
+                        if target < buf.len_lines() {
+                            buf.insert(buf.line_to_char(target), &text);
+                        } else {
+                            //below the last line, which has no line break to put it after
+                            text.pop();
+                            text.insert(0, '\n');
+                            buf.insert(buf.len_chars(), &text);
+                        }
+                        cursor.x = 0;
+                        cursor.y = target as u32;
+                    }
+                    RegionKind::Block => {
+                        let x = if !before && buf.line_len(y) > 0 { x + 1 } else { x };
+                        for (i, line) in reg.text.lines().enumerate() {
+                            let row = y + i;
+                            if row >= buf.len_lines() {
+                                buf.insert(buf.len_chars(), "\n");
+                            }
+                            //short lines get padded out to the column first
+                            let len = buf.line_len(row);
+                            if len < x {
+                                buf.insert(buf.line_to_char(row) + len, &" ".repeat(x - len));
+                            }
+                            buf.insert(buf.line_to_char(row) + x, &line.repeat(count));
+                        }
+                        cursor.x = x as u32;
+                        cursor.y = y as u32;
+                    }
+                }
+            }
+        }
+    }
+    //replaces the region with `text`, a line region keeps the line break after it
+    pub fn set_text(&mut self, region: &Region, text: &str) {
+        match &mut self.buf {
+            BufType::Text{buf, cursor, modified, ..} => {
+                let r = region.normalized();
+                let ranges = buf.region_ranges(&r);
+                let start = ranges[0].start;
+                let mut text = text.to_string();
+                if r.kind == RegionKind::Line && ranges[0].end < buf.len_chars() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                for range in ranges.into_iter().rev() {
+                    buf.remove(range);
+                }
+                buf.insert(start, &text);
+                *modified = true;
+                let (x, y) = buf.char_to_pos(start);
+                cursor.x = x as u32;
+                cursor.y = y as u32;
+            }
+        }
+    }
+    //lines start..end without their line breaks
+    pub fn get_lines(&self, start: usize, end: usize) -> Vec<String> {
+        match &self.buf {
+            BufType::Text{buf, ..} => (start..end).map(|y| buf.line(y).to_string()).collect(),
+        }
+    }
+    //puts `lines` where lines start..end were, start == end adds them before line start,
+    //the cursor stays on the text it was on unless its line is replaced
+    pub fn set_lines(&mut self, start: usize, end: usize, lines: &[String]) {
+        match &mut self.buf {
+            BufType::Text{buf, cursor, modified, ..} => {
+                let n = buf.len_lines();
+                let at = |y: usize| if y < n { buf.line_to_char(y) } else { buf.len_chars() };
+                let (mut from, to) = (at(start), at(end));
+                let mut text = lines.join("\n");
+                if end < n {
+                    if !lines.is_empty() {
+                        text.push('\n');
+                    }
+                } else if start == n {
+                    if !lines.is_empty() {
+                        text.insert(0, '\n');
+                    }
+                } else if start > 0 && lines.is_empty() {
+                    //the last line has no line break of its own, so take the one before it
+                    from -= 1;
+                }
+                if from == to && text.is_empty() {
+                    return;
+                }
+                buf.remove(from..to);
+                buf.insert(from, &text);
+                *modified = true;
+                let y = cursor.y as usize;
+                let y = if y >= end { y + lines.len() - (end - start) } else { y.min(start) };
+                cursor.y = y.min(buf.len_lines() - 1) as u32;
+                cursor.x = cursor.x.min(buf.line_len(cursor.y as usize) as u32);
+            }
+        }
+    }
+    //inserts `text` at x, y and returns the position after it, the cursor moves along when it's past x, y
+    pub fn insert_text(&mut self, x: usize, y: usize, text: &str) -> (usize, usize) {
+        match &mut self.buf {
+            BufType::Text{buf, cursor, modified, ..} => {
+                let idx = buf.pos_to_char(x, y);
+                let mut at = buf.pos_to_char(cursor.x as usize, cursor.y as usize);
+                buf.insert(idx, text);
+                if !text.is_empty() {
+                    *modified = true;
+                }
+                let len = text.chars().count();
+                if at > idx {
+                    at += len;
+                }
+                let (cx, cy) = buf.char_to_pos(at);
+                cursor.x = cx as u32;
+                cursor.y = cy as u32;
+                buf.char_to_pos(idx + len)
+            }
+        }
+    }
+    pub fn char_at(&self, x: usize, y: usize) -> Option<char> {
+        match &self.buf {
+            BufType::Text{buf, ..} => buf.line(y).get_char(x),
+        }
+    }
+    //whether there are edits that haven't been committed to the undo history yet
+    pub fn has_changes(&self) -> bool {
+        match &self.buf {
+            BufType::Text{buf, ..} => buf.has_journal(),
+        }
+    }
+    //ends the current undo group, edits made after this are undone separately
+    pub fn commit_undo(&mut self) {
+        match &mut self.buf {
+            BufType::Text{buf, history, ..} => history.commit(buf.take_journal()),
+        }
+    }
+    //runs a move through the undo tree, then puts the cursor where the text changed
+    fn travel(&mut self, f: impl FnOnce(&mut UndoTree, &mut Text) -> Option<usize>) {
+        self.commit_undo();
+        match &mut self.buf {
+            BufType::Text{buf, cursor, modified, history, ..} => {
+                if let Some(idx) = f(history, buf) {
+                    let (x, y) = buf.char_to_pos(idx.min(buf.len_chars()));
+                    cursor.x = x as u32;
+                    cursor.y = y as u32;
+                }
+                *modified = !history.is_saved();
+            }
+        }
+    }
+    pub fn undo(&mut self) {
+        self.travel(|history, buf| history.undo(buf));
+    }
+    pub fn redo(&mut self) {
+        self.travel(|history, buf| history.redo(buf));
+    }
+    pub fn earlier(&mut self, by: TimeTravel) {
+        self.travel(|history, buf| match by {
+            TimeTravel::Steps(n) => history.earlier(n, buf),
+            TimeTravel::Time(d) => history.earlier_time(d, buf),
+        });
+    }
+    pub fn later(&mut self, by: TimeTravel) {
+        self.travel(|history, buf| match by {
+            TimeTravel::Steps(n) => history.later(n, buf),
+            TimeTravel::Time(d) => history.later_time(d, buf),
+        });
+    }
+    pub fn insert_char(&mut self, c: char, lua: &Lua, config: &mut Config) {
+        //InsertCharPre handlers can swap the char for another one through ev.char, or for "" to drop it
+        let handlers = config.autocmds.handlers(Event::InsertCharPre, &self.event_match());
+        let c = if handlers.is_empty() {
+            c
+        } else {
+            let m = self.event_match();
+            match autocmd::run(lua, handlers, Event::InsertCharPre, &m, Some(self), |ev| ev.set("char", c.to_string())).and_then(|ev| ev.get::<String>("char")) {
+                Ok(s) => match s.chars().next() {
+                    Some(c) => c,
+                    None => return,
+                },
+                Err(e) => {
+                    config.cmdline.error(e);
+                    c
+                }
+            }
+        };
+        match &mut self.buf {
+            BufType::Text{buf, cursor, modified, ..} => {
+                *modified = true;
+                let idx = buf.pos_to_char(cursor.x as usize, cursor.y as usize);
                 if c == '\n' {
-                    let c = buf[cursor.y as usize].cells.split_off(cursor.x as usize);
-                    buf.insert(cursor.y as usize + 1, Line {
-                        cells: c 
-                    });
+                    buf.insert(idx, "\n");
                     cursor.x = 0;
                     cursor.y +=1;
                     return;     
@@ -293,16 +1238,8 @@ impl Pane {
                 } else {
                     insert.push(c);
                 }
-                for ch in insert.chars() {
-                    buf[cursor.y as usize].cells.insert(cursor.x as usize, TextCell { 
-                        char: ch, 
-                        fg: config.text,
-                        bg: None,
-                        font: (font.find_font(&[&config.monospace.clone()]), config.font_size),
-                        font_style: FontStyle::NORMAL,
-                    });
-                    cursor.x+=1;
-                }
+                buf.insert(idx, &insert);
+                cursor.x += insert.chars().count() as u32;
             }
             _=>{}
         }
@@ -310,15 +1247,17 @@ impl Pane {
 
     pub fn backspace(&mut self) {
         match &mut self.buf {
-            BufType::Text{buf, cursor, ..} => {
+            BufType::Text{buf, cursor, modified, ..} => {
+                let idx = buf.pos_to_char(cursor.x as usize, cursor.y as usize);
                 if cursor.x > 0 {
+                    *modified = true;
                     cursor.x-=1;
-                    buf[cursor.y as usize].cells.remove(cursor.x as usize);
+                    buf.remove(idx-1..idx);
                 } else if cursor.y > 0 {
-                    let mut c = buf.remove(cursor.y as usize);
+                    *modified = true;
                     cursor.y -= 1;
-                    cursor.x = buf[cursor.y as usize].cells.len() as u32;
-                    buf[cursor.y as usize].cells.append(&mut c.cells);
+                    cursor.x = buf.line_len(cursor.y as usize) as u32;
+                    buf.remove(idx-1..idx);
                 }
             }
             _=>{}
@@ -326,33 +1265,40 @@ impl Pane {
     }
     pub fn delete_line(&mut self) {
         match &mut self.buf {
-            BufType::Text{buf, cursor,..} => {
-                buf.remove(cursor.y as usize);
+            BufType::Text{buf, cursor, modified, ..} => {
+                *modified = true;
+                let y = cursor.y as usize;
+                let start = buf.line_to_char(y);
+                let end = start + buf.rope().line(y).len_chars();
+                if y + 1 == buf.len_lines() && y > 0 {
+                    //the last line has no line break of its own, so take the one before it
+                    buf.remove(start-1..end);
+                } else {
+                    buf.remove(start..end);
+                }
                 cursor.x = 0;
-                if cursor.y >= buf.len() as u32 {
-                    if cursor.y > 0 {
-                        cursor.y-=1;
-                    }
+                if cursor.y >= buf.len_lines() as u32 && cursor.y > 0 {
+                    cursor.y-=1;
                 }
             }
             _=>{}
         }
     }
-    pub fn handle_events(&mut self, config: &mut Config, fonts: &mut Fonts, keycode: Keycode, keymod: Mod, text: Option<String>, finish: bool) {
+    pub fn handle_events(&mut self, lua: &Lua, config: &mut Config, keycode: Keycode, keymod: Mod, text: Option<String>, finish: bool) {
         match &mut self.buf {
             BufType::Text{..} => {
                 if let Mode::Insert = config.mode {
                     match keycode {
                         Keycode::TAB => {
-                            self.insert_char('\t', config, fonts);
+                            self.insert_char('\t', lua, config);
                             return;
                         }
                         Keycode::Space => {
-                            self.insert_char(' ', config, fonts);
+                            self.insert_char(' ', lua, config);
                             return;
                         }
                         Keycode::Return => {
-                            self.insert_char('\n', config, fonts);
+                            self.insert_char('\n', lua, config);
                             return
                         }
                         Keycode::BACKSPACE => {
@@ -361,7 +1307,7 @@ impl Pane {
                         }
                         _ => {
                             if let Some(str) = &text {
-                                self.insert_char(str.chars().nth(0).unwrap(), config, fonts);
+                                self.insert_char(str.chars().nth(0).unwrap(), lua, config);
                                 return;
                             }
                         }
@@ -375,30 +1321,378 @@ impl Pane {
     pub fn position(&mut self, x: i32, y: i32, w: u32, h: u32) {
         self.rect = Rect::new(x, y, w, h);
     }
+    //the border goes just outside rect and the title gets a bar of its own above it
+    pub fn render_frame(&self, canvas: &mut Canvas, fonts: &mut Fonts, config: &Config, line_height: u32) {
+        if self.border.is_none() && self.title.is_none() {
+            return;
+        }
+        let title_height = if self.title.is_some() { line_height } else { 0 };
+        let pad = if self.border.is_some() { 1 } else { 0 };
+        let outer = Rect::new(
+            self.rect.x - pad,
+            self.rect.y - pad - title_height as i32,
+            self.rect.width() + pad as u32*2,
+            self.rect.height() + pad as u32*2 + title_height,
+        );
+        canvas.set_clip_rect(outer);
+        canvas.set_draw_color(self.border.or(self.bg).unwrap_or(config.theme.bg("Normal")));
+        canvas.fill_rect(outer).unwrap();
+        if let Some(title) = &self.title {
+            let font = (fonts.find_font(&[&config.monospace]), config.font_size);
+            let mut x = self.rect.x;
+            for c in title.chars() {
+                fonts.draw_char(canvas, &font, FontStyle::BOLD, c, config.theme.fg("Normal"), Point::new(x, outer.y + pad));
+                x += fonts.char_size(&font, FontStyle::BOLD, c).0 as i32;
+            }
+        }
+    }
+}
+
+//runs `f` on the registers with the register to use, reg falls back to the one picked with "x
+fn with_registers<R>(lua: &Lua, reg: Option<char>, f: impl FnOnce(&mut Registers, char) -> R) -> mlua::Result<R> {
+    if let Some(c) = reg {
+        Registers::check(c).map_err(Error::runtime)?;
+    }
+    let config = lua.globals().get::<AnyUserData>("config")?;
+    config.borrow_mut_scoped::<Config, _>(|config| {
+        let c = config.registers.resolve(reg);
+        f(&mut config.registers, c)
+    })
 }
 
 impl UserData for Pane {
+    fn add_fields<F: mlua::UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("path", |_, this| Ok(this.path().map(|p| p.to_string_lossy().to_string())));
+        fields.add_field_method_get("modified", |_, this| Ok(this.modified()));
+        fields.add_field_method_get("filetype", |_, this| Ok(this.filetype().map(str::to_string)));
+        fields.add_field_method_set("filetype", |lua, this, value: Option<String>| {
+            let config = lua.globals().get::<AnyUserData>("config")?;
+            config.borrow_mut_scoped::<Config, _>(|config| this.set_filetype(value, &mut config.syntaxes))?.map_err(Error::runtime)
+        });
+        //nil falls back to the config option
+        fields.add_field_method_get("number", |_, this| Ok(this.number));
+        fields.add_field_method_set("number", |_, this, value: Option<bool>| {
+            this.number = value;
+            Ok(())
+        });
+        fields.add_field_method_get("relativenumber", |_, this| Ok(this.relativenumber));
+        fields.add_field_method_set("relativenumber", |_, this, value: Option<bool>| {
+            this.relativenumber = value;
+            Ok(())
+        });
+        fields.add_field_method_get("signcolumn", |_, this| Ok(this.signcolumn.map(|s| s.name())));
+        fields.add_field_method_set("signcolumn", |_, this, value: Option<SignColumn>| {
+            this.signcolumn = value;
+            Ok(())
+        });
+        fields.add_field_method_get("wrap", |_, this| Ok(this.wrap));
+        fields.add_field_method_set("wrap", |_, this, value: Option<bool>| {
+            this.wrap = value;
+            Ok(())
+        });
+        fields.add_field_method_get("top_line", |_, this| Ok(this.top_line));
+        fields.add_field_method_set("top_line", |_, this, value: usize| {
+            this.top_line = value;
+            this.top_row = 0;
+            Ok(())
+        });
+        fields.add_field_method_get("left_col", |_, this| Ok(this.left_col));
+        fields.add_field_method_set("left_col", |_, this, value: usize| {
+            this.left_col = value;
+            Ok(())
+        });
+        fields.add_field_method_get("z_index", |_, this| Ok(this.z_index));
+        //a pane can't move between the layout and the floating ones by changing this
+        fields.add_field_method_set("z_index", |_, this, value: u32| {
+            if this.z_index == 0 || value == 0 {
+                return Err(Error::runtime("only floating panes can change z_index, and it has to stay above 0"));
+            }
+            this.z_index = value;
+            Ok(())
+        });
+        fields.add_field_method_get("title", |_, this| Ok(this.title.clone()));
+        fields.add_field_method_set("title", |_, this, value: Option<String>| {
+            this.title = value;
+            Ok(())
+        });
+        fields.add_field_method_get("border", |_, this| Ok(this.border.map(crate::from_rgba)));
+        fields.add_field_method_set("border", |_, this, value: Option<u32>| {
+            this.border = value.map(crate::rgba);
+            Ok(())
+        });
+        fields.add_field_method_get("rows", |_, this| Ok(this.rows));
+        fields.add_field_method_get("cols", |_, this| Ok(this.cols));
+        fields.add_field_method_set("modified", |_, this, value: bool| {
+            match &mut this.buf {
+                BufType::Text{modified, ..} => *modified = value,
+            }
+            Ok(())
+        });
+    }
+    //x and y in the buffer count from 0, x in chars, and line ranges include start but not end,
+    //so get_lines(0, pane:line_count()) is every line, panes are numbered from 1 like lua lists
     fn add_methods<M: mlua::UserDataMethods<Self>>(methods: &mut M) {
         methods.add_method_mut("set_cursor", |_, this, (x, y): (u32, u32)| {
+            check_line(this, y as usize)?;
             this.set_cursor(x, y);
             Ok(())
         });
         methods.add_method("get_cursor", |_, this, ()| {
             Ok(this.get_cursor())
         });
+        //where things are on screen, rows count the screen rows a wrapped line is drawn on from 0
+        methods.add_method("screen_rows", |lua, this, y: usize| {
+            let config = lua.globals().get::<AnyUserData>("config")?;
+            config.borrow_scoped::<Config, _>(|config| this.line_rows(y.min(this.line_count() - 1), config).len())
+        });
+        methods.add_method("screen_pos", |lua, this, (x, y): (usize, usize)| {
+            let config = lua.globals().get::<AnyUserData>("config")?;
+            config.borrow_scoped::<Config, _>(|config| this.screen_pos(x, y.min(this.line_count() - 1), config))
+        });
+        methods.add_method("screen_char", |lua, this, (y, row, col): (usize, usize, usize)| {
+            let config = lua.globals().get::<AnyUserData>("config")?;
+            config.borrow_scoped::<Config, _>(|config| this.screen_char(y.min(this.line_count() - 1), row, col, config))
+        });
+        //tiled panes get their rect from the layout, so moving them only lasts until the next frame
+        methods.add_method_mut("set_rect", |_, this, (x, y, w, h): (i32, i32, u32, u32)| {
+            this.position(x, y, w.max(1), h.max(1));
+            Ok(())
+        });
+        methods.add_method("get_rect", |_, this, ()| {
+            Ok((this.rect.x, this.rect.y, this.rect.width(), this.rect.height()))
+        });
 
         methods.add_method_mut("delete_line", |_, this, ()| {
             Ok(this.delete_line())
         });
-        methods.add_method("linelen", |_, this, (line,): (usize,)| {
-            Ok(match &this.buf {
+        methods.add_method_mut("scroll", |lua, this, (dx, dy): (isize, isize)| {
+            let config = lua.globals().get::<AnyUserData>("config")?;
+            config.borrow_scoped::<Config, _>(|config| this.scroll(dx, dy, config))
+        });
+        //start is the anchor and end is the cursor, so start can come after end
+        methods.add_method("get_selection", |_, this, ()| {
+            Ok(this.selection())
+        });
+        methods.add_method_mut("set_selection", |_, this, region: Option<Region>| {
+            this.set_selection(region);
+            Ok(())
+        });
+        methods.add_method("get_text", |_, this, region: Region| {
+            Ok(this.get_text(&region))
+        });
+        methods.add_method_mut("set_text", |_, this, (region, text): (Region, String)| {
+            this.set_text(&region, &text);
+            Ok(())
+        });
+        //reg is the register to use, without it the one picked with "x or the unnamed one is used
+        methods.add_method_mut("delete", |lua, this, (region, reg): (Region, Option<char>)| {
+            let kind = region.kind;
+            let text = this.delete(&region);
+            with_registers(lua, reg, |registers, c| registers.delete(c, Register { text: text.clone(), kind }))?;
+            Ok(text)
+        });
+        //like delete, but a line region leaves an empty line behind to type into
+        methods.add_method_mut("change", |lua, this, (region, reg): (Region, Option<char>)| {
+            let text = this.get_text(&region);
+            let kind = region.kind;
+            this.set_text(&region, "");
+            with_registers(lua, reg, |registers, c| registers.delete(c, Register { text: text.clone(), kind }))?;
+            Ok(text)
+        });
+        methods.add_method("yank", |lua, this, (region, reg): (Region, Option<char>)| {
+            let text = this.get_text(&region);
+            with_registers(lua, reg, |registers, c| registers.yank(c, Register { text, kind: region.kind }))
+        });
+        methods.add_method_mut("put", |lua, this, (reg, before, count): (Option<char>, Option<bool>, Option<usize>)| {
+            let reg = with_registers(lua, reg, |registers, c| registers.get(c).ok_or(c))?;
+            match reg {
+                Ok(reg) => {
+                    this.put(&reg, before.unwrap_or(false), count.unwrap_or(1).max(1));
+                    Ok(())
+                }
+                Err(c) => Err(Error::runtime(format!("nothing in register {c}"))),
+            }
+        });
+        //replaces the matches of pattern on range, which is a line, a {start, end} table of lines like get_lines takes or nil for the cursor line
+        //flags are g for every match on a line, c to confirm each one, n to only count them, and i or I to ignore or match case
+        //returns how many matches there were
+        methods.add_method_mut("substitute", |lua, this, (range, pattern, replacement, flags): (Value, String, String, Option<String>)| {
+            let flags = Flags::parse(&flags.unwrap_or_default()).map_err(Error::runtime)?;
+            let (start, end) = match range {
+                Value::Nil => {
+                    let y = this.get_cursor().1 as usize;
+                    (y, y + 1)
+                }
+                Value::Table(t) => (t.get(1)?, t.get(2)?),
+                v => {
+                    let y = usize::from_lua(v, lua)?;
+                    (y, y + 1)
+                }
+            };
+            check_lines(this, start, end)?;
+            if start == end {
+                return Ok(0);
+            }
+            let lines = (start, end - 1);
+            let config = lua.globals().get::<AnyUserData>("config")?;
+            let case = config.borrow_scoped::<Config, _>(|config| flags.case.unwrap_or(config.last_search.case))?;
+            let re = search::compile(&pattern, case).map_err(Error::external)?;
+            let n = this.substitute(&re, lines, &replacement, &flags);
+            let message = match &this.confirm {
+                Some(confirm) if flags.confirm => Some(confirm.prompt()),
+                _ if flags.count => Some(format!("{n} match{}", if n == 1 { "" } else { "es" })),
+                _ if n > 1 => Some(substitute::report(n)),
+                _ => None,
+            };
+            if message.is_some() {
+                config.borrow_mut_scoped::<Config, _>(|config| config.cmdline.message = message)?;
+            }
+            Ok(n)
+        });
+        //every match of the pattern as a region, empty matches are left out
+        //opts.case is "smart", "ignore" or "match" and defaults to config.search_case
+        methods.add_method("search", |lua, this, (pattern, opts): (String, Option<Table>)| {
+            let case = match opts.map(|o| o.get::<Option<String>>("case")).transpose()?.flatten() {
+                Some(name) => Case::from_name(&name).map_err(Error::runtime)?,
+                None => {
+                    let config = lua.globals().get::<AnyUserData>("config")?;
+                    config.borrow_scoped::<Config, _>(|config| config.last_search.case)?
+                }
+            };
+            let re = search::compile(&pattern, case).map_err(Error::external)?;
+            let BufType::Text{buf, ..} = &this.buf;
+            Ok(buf.find(&re, 0..buf.len_chars()).into_iter().filter(|m| !m.is_empty()).map(|m| Region {
+                start: buf.char_to_pos(m.start),
+                end: buf.char_to_pos(m.end - 1),
+                kind: RegionKind::Char,
+            }).collect::<Vec<_>>())
+        });
+        //where the count'th match of the last search is, reverse goes the other way like N
+        methods.add_method("next_match", |lua, this, (count, reverse): (Option<usize>, Option<bool>)| {
+            let config = lua.globals().get::<AnyUserData>("config")?;
+            config.borrow_mut_scoped::<Config, _>(|config| {
+                config.last_search.hidden = false;
+                config.last_search.next(this, count.unwrap_or(1).max(1), reverse.unwrap_or(false))
+            })?.map_err(Error::runtime)
+        });
+        methods.add_method_mut("undo", |_, this, ()| {
+            this.undo();
+            Ok(())
+        });
+        methods.add_method_mut("redo", |_, this, ()| {
+            this.redo();
+            Ok(())
+        });
+        methods.add_method_mut("earlier", |_, this, by: TimeTravel| {
+            this.earlier(by);
+            Ok(())
+        });
+        methods.add_method_mut("later", |_, this, by: TimeTravel| {
+            this.later(by);
+            Ok(())
+        });
+        //BufWritePre handlers get the pane to change before it's written
+        methods.add_method_mut("save", |lua, this, path: Option<String>| {
+            let path = path.map(PathBuf::from);
+            let m = path.as_ref().or(this.path()).map_or(String::new(), |p| p.to_string_lossy().to_string());
+            let config = lua.globals().get::<AnyUserData>("config")?;
+            let handlers = config.borrow_scoped::<Config, _>(|config| config.autocmds.handlers(Event::BufWritePre, &m))?;
+            autocmd::run(lua, handlers, Event::BufWritePre, &m, Some(this), |ev| ev.set("path", m.as_str()))?;
+            this.save(path).map_err(Error::external)
+        });
+        methods.add_method_mut("open", |lua, this, (path, force): (String, Option<bool>)| {
+            if this.modified() && !force.unwrap_or(false) {
+                return Err(Error::runtime("no write since last change (pass force to discard)"));
+            }
+            let config = lua.globals().get::<AnyUserData>("config")?;
+            config.borrow_mut_scoped::<Config, _>(|config| config.open(this, PathBuf::from(path)))?.map_err(Error::external)
+        });
+        //x past the end of a line is taken as the end of it, lines past the end are an error
+        methods.add_method_mut("highlight", |_, this, (x1, y1, x2, y2, style): (usize, usize, usize, usize, Style)| {
+            check_line(this, y1)?;
+            check_line(this, y2)?;
+            match &mut this.buf {
+                BufType::Text{buf, ..} => {
+                    let start = buf.pos_to_char(x1, y1);
+                    let end = buf.pos_to_char(x2, y2);
+                    if start > end {
+                        return Err(Error::runtime(format!("highlight start {x1}, {y1} comes after its end {x2}, {y2}")));
+                    }
+                    buf.add_span(start..end, style);
+                }
+            }
+            Ok(())
+        });
+        //a nil text takes the sign off the line
+        methods.add_method_mut("set_sign", |_, this, (line, text, hl): (usize, Option<String>, Option<String>)| {
+            check_line(this, line)?;
+            match &mut this.buf {
                 BufType::Text{buf, ..} => {
-                    buf[line].cells.len()
+                    let hl = hl.unwrap_or_else(|| "SignColumn".to_string());
+                    buf.set_sign(line, text.map(|text| (text, hl)));
                 }
-                _ => 0
-            })
-        })
+            }
+            Ok(())
+        });
+        methods.add_method_mut("clear_signs", |_, this, ()| {
+            match &mut this.buf {
+                BufType::Text{buf, ..} => buf.clear_signs(),
+            }
+            Ok(())
+        });
+        methods.add_method_mut("clear_highlights", |_, this, ()| {
+            match &mut this.buf {
+                BufType::Text{buf, ..} => buf.clear_spans(),
+            }
+            Ok(())
+        });
+        methods.add_method("line_count", |_, this, ()| Ok(this.line_count()));
+        methods.add_method("linelen", |_, this, y: usize| {
+            check_line(this, y)?;
+            Ok(this.line_len(y))
+        });
+        methods.add_method("char_at", |_, this, (x, y): (usize, usize)| {
+            check_line(this, y)?;
+            Ok(this.char_at(x, y).map(String::from))
+        });
+        methods.add_method("get_lines", |_, this, (start, end): (usize, Option<usize>)| {
+            let end = end.unwrap_or(this.line_count());
+            check_lines(this, start, end)?;
+            Ok(this.get_lines(start, end))
+        });
+        methods.add_method_mut("set_lines", |_, this, (start, end, lines): (usize, usize, Vec<String>)| {
+            check_lines(this, start, end)?;
+            this.set_lines(start, end, &lines);
+            Ok(())
+        });
+        //pos is {x = , y = }, x can be the line's length to add to the end of it, the position after the text comes back the same way
+        methods.add_method_mut("insert", |lua, this, (pos, text): (Table, String)| {
+            let (x, y): (usize, usize) = (pos.get("x")?, pos.get("y")?);
+            check_line(this, y)?;
+            if x > this.line_len(y) {
+                return Err(Error::runtime(format!("column {x} is past the end of line {y}, which is {} long", this.line_len(y))));
+            }
+            let (x, y) = this.insert_text(x, y, &text);
+            let end = lua.create_table()?;
+            end.set("x", x)?;
+            end.set("y", y)?;
+            Ok(end)
+        });
+    }
+}
+
+fn check_line(pane: &Pane, y: usize) -> mlua::Result<()> {
+    if y >= pane.line_count() {
+        return Err(Error::runtime(format!("line {y} is out of range, the pane has {} lines", pane.line_count())));
+    }
+    Ok(())
+}
+
+fn check_lines(pane: &Pane, start: usize, end: usize) -> mlua::Result<()> {
+    if start > end || end > pane.line_count() {
+        return Err(Error::runtime(format!("lines {start}..{end} are out of range, the pane has {} lines", pane.line_count())));
     }
+    Ok(())
 }
 
 //TASK(20260114-132528-029-n6-460): make visual mode work
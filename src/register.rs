@@ -0,0 +1,162 @@
+//vim style registers, the unnamed one, a-z, the numbered delete history, "_ and the clipboard
+use std::collections::HashMap;
+
+use mlua::{FromLua, IntoLua, Lua, Value};
+use sdl2::clipboard::ClipboardUtil;
+
+use crate::text::RegionKind;
+
+#[derive(Clone, Debug)]
+pub struct Register {
+    pub text: String,
+    //decides how the text gets put back, whole lines go on lines of their own
+    pub kind: RegionKind,
+}
+
+impl FromLua for Register {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+        match value {
+            Value::String(s) => {
+                let text = s.to_str()?.to_string();
+                let kind = if text.ends_with('\n') { RegionKind::Line } else { RegionKind::Char };
+                Ok(Self { text, kind })
+            }
+            Value::Table(t) => {
+                let kind = match t.get::<Option<String>>("kind")?.as_deref() {
+                    None | Some("char") => RegionKind::Char,
+                    Some("line") => RegionKind::Line,
+                    Some("block") => RegionKind::Block,
+                    Some(other) => return Err(mlua::Error::runtime(format!("unknown register kind {other}"))),
+                };
+                Ok(Self { text: t.get("text")?, kind })
+            }
+            _ => Err(mlua::Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Register".to_string(),
+                message: Some("register must be a string or a table of text and kind".to_string()),
+            }),
+        }
+    }
+}
+
+impl IntoLua for Register {
+    fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+        let t = lua.create_table()?;
+        t.set("text", self.text)?;
+        t.set("kind", self.kind.name())?;
+        Ok(Value::Table(t))
+    }
+}
+
+pub struct Registers {
+    regs: HashMap<char, Register>,
+    //set once the video subsystem is up, "+ and "* are empty without it
+    pub clipboard: Option<ClipboardUtil>,
+    //picked with "x, the next yank, delete or put uses it
+    pub selected: Option<char>,
+}
+
+impl Registers {
+    pub fn new() -> Self {
+        Self {
+            regs: HashMap::new(),
+            clipboard: None,
+            selected: None,
+        }
+    }
+    pub fn valid(c: char) -> bool {
+        c.is_ascii_alphanumeric() || "\"-_+*".contains(c)
+    }
+    pub fn check(c: char) -> Result<char, String> {
+        if Self::valid(c) {
+            Ok(c)
+        } else {
+            Err(format!("invalid register {c:?}"))
+        }
+    }
+    //the register to use when none was given, which also uses up a "x prefix
+    pub fn resolve(&mut self, c: Option<char>) -> char {
+        c.or(self.selected.take()).unwrap_or('"')
+    }
+    pub fn get(&self, c: char) -> Option<Register> {
+        match c {
+            '_' => None,
+            '+' | '*' => {
+                let text = self.clipboard.as_ref()?.clipboard_text().ok()?;
+                let kind = if text.ends_with('\n') { RegionKind::Line } else { RegionKind::Char };
+                Some(Register { text, kind })
+            }
+            c => self.regs.get(&c.to_ascii_lowercase()).cloned(),
+        }
+    }
+    //stores into exactly one register, uppercase letters append to the lowercase one
+    pub fn set(&mut self, c: char, reg: Register) {
+        match c {
+            '_' => {}
+            '+' | '*' => {
+                if let Some(clipboard) = &self.clipboard {
+                    clipboard.set_clipboard_text(&reg.text).ok();
+                }
+            }
+            'A'..='Z' => {
+                let c = c.to_ascii_lowercase();
+                let reg = match self.regs.remove(&c) {
+                    Some(mut old) => {
+                        if (old.kind == RegionKind::Line || reg.kind == RegionKind::Line) && !old.text.ends_with('\n') {
+                            old.text.push('\n');
+                        }
+                        if reg.kind == RegionKind::Line {
+                            old.kind = RegionKind::Line;
+                        }
+                        old.text.push_str(&reg.text);
+                        old
+                    }
+                    None => reg,
+                };
+                self.regs.insert(c, reg);
+            }
+            c => {
+                self.regs.insert(c, reg);
+            }
+        }
+    }
+    //after a yank or delete the unnamed register holds what the named one ended up with
+    fn point_unnamed(&mut self, c: char, reg: Register) {
+        let reg = if c == '"' { reg } else { self.get(c).unwrap_or(reg) };
+        self.regs.insert('"', reg);
+    }
+    //yanks go to "0 unless a register was named, and always to the unnamed one
+    pub fn yank(&mut self, c: char, reg: Register) {
+        if c == '_' {
+            return;
+        }
+        if c == '"' {
+            self.set('0', reg.clone());
+        } else {
+            self.set(c, reg.clone());
+        }
+        self.point_unnamed(c, reg);
+    }
+    //deleted lines shift through "1 to "9 even when a register was named, smaller deletes go to "- unless one was
+    pub fn delete(&mut self, c: char, reg: Register) {
+        if c == '_' {
+            return;
+        }
+        if c != '"' {
+            self.set(c, reg.clone());
+        }
+        if reg.kind == RegionKind::Line || reg.text.contains('\n') {
+            for n in (1..9).rev() {
+                let from = char::from_digit(n, 10).unwrap();
+                let to = char::from_digit(n + 1, 10).unwrap();
+                if let Some(r) = self.regs.remove(&from) {
+                    self.regs.insert(to, r);
+                }
+            }
+            self.set('1', reg.clone());
+        } else if c == '"' {
+            self.set('-', reg.clone());
+        }
+        self.point_unnamed(c, reg);
+    }
+}
@@ -0,0 +1,59 @@
+//running default.lua and init.lua, and running them again when the config changes
+use std::path::Path;
+
+use mlua::{Error, Function, Lua, Table, Value};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use sdl2::event::EventSender;
+
+use crate::{config_dir, font::Fonts, jobs, Config};
+
+//runs default.lua and then init.lua, if init.lua fails whatever it did is thrown away so a typo can't leave the editor half set up
+pub fn load(lua: &Lua, config: &mut Config, fonts: &mut Fonts) -> mlua::Result<()> {
+    lua.load(include_str!("./default.lua")).set_name("=default.lua").exec()?;
+    let init_file = config_dir().join("init.lua");
+    let init = match std::fs::read_to_string(&init_file) {
+        Ok(text) => lua.load(&text).set_name(format!("@{}", init_file.display())).exec(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(Error::external(e)),
+    };
+    if let Err(e) = init {
+        config.reset(fonts);
+        lua.load(include_str!("./default.lua")).set_name("=default.lua").exec()?;
+        config.cmdline.error(format!("init.lua failed, only the defaults are loaded: {e}"));
+    }
+    Ok(())
+}
+
+//forgets the modules that came from `dir`, so the next require reads them again
+pub fn flush_modules(lua: &Lua, dir: &Path) -> mlua::Result<()> {
+    let package: Table = lua.globals().get("package")?;
+    let loaded: Table = package.get("loaded")?;
+    let searchpath: Function = package.get("searchpath")?;
+    let path: String = package.get("path")?;
+    let names: Vec<String> = loaded.pairs::<Value, Value>().filter_map(|pair| {
+        let (name, _) = pair.ok()?;
+        name.as_string().map(|s| s.to_string_lossy())
+    }).collect();
+    for name in names {
+        let found: Option<String> = searchpath.call((name.as_str(), path.as_str()))?;
+        if found.is_some_and(|f| Path::new(&f).starts_with(dir)) {
+            loaded.set(name, Value::Nil)?;
+        }
+    }
+    Ok(())
+}
+
+//watches the config dir and wakes the event loop with an event of `kind` when a lua file in it changes
+pub fn watch(sender: EventSender, kind: u32) -> notify::Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        let lua = event.paths.iter().any(|p| p.extension().is_some_and(|e| e == "lua"));
+        if lua && matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            jobs::wake(&sender, kind);
+        }
+    })?;
+    watcher.watch(&config_dir(), RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
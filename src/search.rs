@@ -0,0 +1,116 @@
+//regex search, the / and ? prompts move the cursor as the pattern is typed
+use regex::{Regex, RegexBuilder};
+
+use crate::{cmdline::CmdLineEvent, pane::Pane};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Case {
+    //ignore case unless the pattern has an uppercase letter in it
+    Smart,
+    Ignore,
+    Match,
+}
+
+impl Case {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "smart" => Ok(Self::Smart),
+            "ignore" => Ok(Self::Ignore),
+            "match" => Ok(Self::Match),
+            _ => Err(format!("unknown case {name:?}, expected \"smart\", \"ignore\" or \"match\"")),
+        }
+    }
+}
+
+pub fn compile(pattern: &str, case: Case) -> Result<Regex, regex::Error> {
+    let ignore = match case {
+        Case::Smart => !pattern.chars().any(char::is_uppercase),
+        Case::Ignore => true,
+        Case::Match => false,
+    };
+    RegexBuilder::new(pattern).case_insensitive(ignore).multi_line(true).build()
+}
+
+pub struct Search {
+    //the last pattern searched for and whether it went backwards, n goes the same way and N the other
+    pub pattern: Option<String>,
+    pub backward: bool,
+    pub case: Case,
+    pub hlsearch: bool,
+    //set by :nohlsearch, the matches show again on the next search
+    pub hidden: bool,
+    //where the cursor was when the prompt opened, it goes back there if the search is cancelled
+    origin: Option<(u32, u32)>,
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self {
+            pattern: None,
+            backward: false,
+            case: Case::Smart,
+            hlsearch: true,
+            hidden: false,
+            origin: None,
+        }
+    }
+    pub fn is_prompt(prompt: char) -> bool {
+        prompt == '/' || prompt == '?'
+    }
+    //the pattern to highlight, the one being typed wins over the last one
+    pub fn highlight(&self, typing: Option<String>) -> Option<Regex> {
+        let pattern = match typing {
+            Some(p) => p,
+            None if self.hlsearch && !self.hidden => self.pattern.clone()?,
+            None => return None,
+        };
+        if pattern.is_empty() {
+            return None;
+        }
+        compile(&pattern, self.case).ok()
+    }
+    //moves the cursor for an event at the search prompt, returns a message for the command line
+    pub fn prompt_event(&mut self, prompt: char, event: &CmdLineEvent, text: &str, pane: &mut Pane) -> Option<String> {
+        let backward = prompt == '?';
+        let origin = *self.origin.get_or_insert(pane.get_cursor());
+        pane.set_cursor(origin.0, origin.1);
+        match event {
+            CmdLineEvent::Edited => {
+                if let Ok(re) = compile(text, self.case)
+                    && !text.is_empty()
+                    && let Some((x, y)) = pane.search(&re, backward, 1)
+                {
+                    pane.set_cursor(x as u32, y as u32);
+                }
+                None
+            }
+            CmdLineEvent::Submit(_) => {
+                self.origin = None;
+                //an empty pattern searches for the last one again
+                if !text.is_empty() {
+                    self.pattern = Some(text.to_string());
+                }
+                self.backward = backward;
+                self.hidden = false;
+                self.find(pane, 1, false).err()
+            }
+            _ => {
+                self.origin = None;
+                None
+            }
+        }
+    }
+    //moves to the count'th match of the last pattern, reverse goes the other way like N
+    pub fn find(&self, pane: &mut Pane, count: usize, reverse: bool) -> Result<(), String> {
+        let (x, y) = self.next(pane, count, reverse)?;
+        pane.set_cursor(x as u32, y as u32);
+        Ok(())
+    }
+    pub fn next(&self, pane: &Pane, count: usize, reverse: bool) -> Result<(usize, usize), String> {
+        let Some(pattern) = &self.pattern else {
+            return Err("no previous search pattern".to_string());
+        };
+        let re = compile(pattern, self.case).map_err(|e| e.to_string())?;
+        pane.search(&re, self.backward != reverse, count).ok_or_else(|| format!("pattern not found: {pattern}"))
+    }
+}
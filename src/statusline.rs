@@ -0,0 +1,102 @@
+//the status line under each tiled pane and the tab line over the layout, both are drawn from what a lua function returns
+use mlua::{FromLua, Function, IntoLuaMulti, Lua, Value};
+use sdl2::rect::{Point, Rect};
+
+use crate::{font::Fonts, text::Style, Config};
+
+type Canvas = sdl2::render::Canvas<sdl2::video::Window>;
+
+pub struct Segment {
+    pub text: String,
+    //a highlight group drawn over the line's own group, then the style is laid over that
+    pub hl: Option<String>,
+    pub style: Style,
+}
+
+//a segment is a plain string, or a table like {"text", hl = "Search"} or {"text", fg = 0xff0000ff, bold = true}
+impl FromLua for Segment {
+    fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+        match value {
+            Value::Table(t) => Ok(Self {
+                text: t.get::<Option<String>>(1)?.unwrap_or_default(),
+                hl: t.get("hl")?,
+                style: Style::from_lua(Value::Table(t), lua)?,
+            }),
+            value => Ok(Self {
+                text: String::from_lua(value, lua)?,
+                hl: None,
+                style: Style::default(),
+            }),
+        }
+    }
+}
+
+//what a line function gave back, segments pushed to the left and ones pushed to the right
+pub struct Line {
+    pub left: Vec<Segment>,
+    pub right: Vec<Segment>,
+}
+
+impl Line {
+    //calls `func`, which returns a list of segments for the left and optionally one for the right,
+    //an error is shown on the line instead so a broken function doesn't take the editor down
+    pub fn call(func: &Function, args: impl IntoLuaMulti) -> Self {
+        let result = func.call::<(Option<Vec<Segment>>, Option<Vec<Segment>>)>(args);
+        match result {
+            Ok((left, right)) => Self { left: left.unwrap_or_default(), right: right.unwrap_or_default() },
+            Err(e) => Self {
+                left: vec![Segment {
+                    text: e.to_string().lines().next().unwrap_or("").to_string(),
+                    hl: Some("Error".to_string()),
+                    style: Style::default(),
+                }],
+                right: Vec::new(),
+            },
+        }
+    }
+    //fills `rect` with `group` and draws the left segments from the left edge and the right ones up to the right edge
+    pub fn render(&self, canvas: &mut Canvas, fonts: &mut Fonts, config: &Config, rect: Rect, group: &str) {
+        canvas.set_clip_rect(rect);
+        let base = config.theme.get(group);
+        canvas.set_draw_color(config.theme.bg(group));
+        canvas.fill_rect(rect).unwrap();
+        let font = (fonts.find_font(&[&config.monospace]), config.font_size);
+        let style = |segment: &Segment| {
+            let mut style = base;
+            if let Some(hl) = &segment.hl {
+                style.merge(&config.theme.get(hl));
+            }
+            style.merge(&segment.style);
+            style
+        };
+        let width = |fonts: &mut Fonts, segments: &[Segment]| -> i32 {
+            segments.iter().map(|s| {
+                let font_style = style(s).font_style;
+                s.text.chars().map(|c| fonts.char_size(&font, font_style, c).0 as i32).sum::<i32>()
+            }).sum()
+        };
+        //the left side wins when they don't both fit
+        let left_end = rect.x + width(fonts, &self.left);
+        let mut x = (rect.right() - width(fonts, &self.right)).max(left_end);
+        let draw = |fonts: &mut Fonts, canvas: &mut Canvas, segment: &Segment, x: &mut i32| {
+            let style = style(segment);
+            let fg = style.fg.unwrap_or(config.theme.fg(group));
+            for c in segment.text.chars() {
+                let w = fonts.char_size(&font, style.font_style, c).0;
+                if let Some(bg) = style.bg {
+                    canvas.set_draw_color(bg);
+                    canvas.fill_rect(Rect::new(*x, rect.y, w.max(1), rect.height())).unwrap();
+                }
+                fonts.draw_char(canvas, &font, style.font_style, c, fg, Point::new(*x, rect.y));
+                *x += w as i32;
+            }
+        };
+        for segment in &self.right {
+            draw(fonts, canvas, segment, &mut x);
+        }
+        let mut x = rect.x;
+        for segment in &self.left {
+            draw(fonts, canvas, segment, &mut x);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//:s, replacing the matches of a pattern line by line, optionally asking about each one
+use std::ops::Range;
+
+use regex::{Captures, Regex};
+
+use crate::{search::Case, text::Text};
+
+pub struct Flags {
+    //every match on a line instead of only the first
+    pub global: bool,
+    pub confirm: bool,
+    //only count the matches
+    pub count: bool,
+    pub case: Option<Case>,
+}
+
+impl Flags {
+    pub fn parse(flags: &str) -> Result<Self, String> {
+        let mut out = Self { global: false, confirm: false, count: false, case: None };
+        for c in flags.chars() {
+            match c {
+                'g' => out.global = true,
+                'c' => out.confirm = true,
+                'n' => out.count = true,
+                'i' => out.case = Some(Case::Ignore),
+                'I' => out.case = Some(Case::Match),
+                c => return Err(format!("unknown substitute flag {c:?}")),
+            }
+        }
+        Ok(out)
+    }
+}
+
+//& and \0 are the whole match, \1 to \9 the groups, \n and \t a line break and a tab, \ before anything else takes it literally
+pub fn expand(rep: &str, caps: &Captures) -> String {
+    let group = |n: usize| caps.get(n).map_or("", |m| m.as_str());
+    let mut out = String::new();
+    let mut chars = rep.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => out.push_str(group(0)),
+            '\\' => match chars.next() {
+                Some(d @ '0'..='9') => out.push_str(group(d as usize - '0' as usize)),
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub struct Replacement {
+    //chars in the buffer as it was when the matches were found
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+//the matches on lines `first..=last`, only the first one on each line unless global
+pub fn matches(buf: &Text, re: &Regex, (first, last): (usize, usize), rep: &str, global: bool) -> Vec<Replacement> {
+    let start = buf.line_to_char(first);
+    let slice = buf.rope().slice(start..buf.line_to_char(last) + buf.line_len(last));
+    let text = slice.to_string();
+    let mut out = Vec::new();
+    let mut prev_line = None;
+    for caps in re.captures_iter(&text) {
+        let m = caps.get(0).unwrap();
+        let range = start + slice.byte_to_char(m.start())..start + slice.byte_to_char(m.end());
+        let line = buf.rope().char_to_line(range.start);
+        if !global && prev_line == Some(line) {
+            continue;
+        }
+        prev_line = Some(line);
+        out.push(Replacement { range, text: expand(rep, &caps) });
+    }
+    out
+}
+
+fn shifted(range: &Range<usize>, shift: isize) -> Range<usize> {
+    range.start.saturating_add_signed(shift)..range.end.saturating_add_signed(shift)
+}
+
+//replaces one match, `shift` is how far earlier replacements moved it, returns the shift for the ones after it
+pub fn apply(buf: &mut Text, r: &Replacement, shift: isize) -> isize {
+    let len = buf.len_chars();
+    let range = shifted(&r.range, shift);
+    let range = range.start.min(len)..range.end.min(len);
+    buf.remove(range.clone());
+    buf.insert(range.start, &r.text);
+    shift + r.text.chars().count() as isize - range.len() as isize
+}
+
+pub fn report(n: usize) -> String {
+    format!("{n} substitution{}", if n == 1 { "" } else { "s" })
+}
+
+//the matches of :s///c still waiting for an answer
+pub struct Confirm {
+    pending: Vec<Replacement>,
+    next: usize,
+    shift: isize,
+    pub done: usize,
+    //the buffer's tick after the last replacement, anything else changing it makes the ranges wrong
+    tick: usize,
+}
+
+impl Confirm {
+    pub fn new(pending: Vec<Replacement>, buf: &Text) -> Self {
+        Self { pending, next: 0, shift: 0, done: 0, tick: buf.tick() }
+    }
+    //whether something other than the replacements edited the buffer while it waited
+    pub fn stale(&self, buf: &Text) -> bool {
+        buf.tick() != self.tick
+    }
+    //where the match being asked about is now
+    pub fn current(&self) -> Option<Range<usize>> {
+        self.pending.get(self.next).map(|r| shifted(&r.range, self.shift))
+    }
+    pub fn prompt(&self) -> String {
+        let text = self.pending.get(self.next).map_or(String::new(), |r| r.text.replace('\n', "\\n"));
+        format!("replace with {text} (y/n/a/q/l)?")
+    }
+    //replaces the next `n` matches
+    pub fn replace(&mut self, buf: &mut Text, n: usize) {
+        for _ in 0..n {
+            let Some(r) = self.pending.get(self.next) else {
+                break;
+            };
+            self.shift = apply(buf, r, self.shift);
+            self.next += 1;
+            self.done += 1;
+        }
+        self.tick = buf.tick();
+    }
+    pub fn skip(&mut self) {
+        self.next += 1;
+    }
+    pub fn quit(&mut self) {
+        self.next = self.pending.len();
+    }
+}
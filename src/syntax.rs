@@ -0,0 +1,193 @@
+//tree-sitter highlighting, grammars are registered by filetype and each buffer keeps its own tree
+use std::{collections::HashMap, fmt, ops::Range, path::Path, rc::Rc};
+
+use libloading::Library;
+use ropey::Rope;
+use tree_sitter::{InputEdit, Language, Node, Parser, Point, Query, QueryCursor, StreamingIterator, Tree};
+
+use crate::{text::Style, theme::Theme};
+
+pub struct Grammar {
+    pub name: String,
+    language: Language,
+    query: Query,
+    //kept so the query can be swapped without reloading the language
+    source: String,
+    //a grammar loaded from a shared library has to outlive everything made from its language
+    library: Option<Rc<Library>>,
+}
+
+impl Grammar {
+    fn new(name: &str, language: Language, source: &str, library: Option<Rc<Library>>) -> Result<Self, String> {
+        let query = Query::new(&language, source).map_err(|e| format!("bad highlight query for {name}: {e}"))?;
+        Ok(Self { name: name.to_string(), language, query, source: source.to_string(), library })
+    }
+}
+
+//the grammars and which file extensions use them
+pub struct Syntaxes {
+    grammars: HashMap<String, Rc<Grammar>>,
+    extensions: HashMap<String, String>,
+}
+
+impl Syntaxes {
+    //a built in grammar whose query won't compile is left out, with the error given back to be shown
+    pub fn new() -> (Self, Vec<String>) {
+        let mut errors = Vec::new();
+        let mut out = Self {
+            grammars: HashMap::new(),
+            extensions: HashMap::new(),
+        };
+        let builtin = [
+            ("rust", Language::new(tree_sitter_rust::LANGUAGE), tree_sitter_rust::HIGHLIGHTS_QUERY, &["rs"][..]),
+            ("toml", Language::new(tree_sitter_toml_ng::LANGUAGE), tree_sitter_toml_ng::HIGHLIGHTS_QUERY, &["toml"]),
+            //only the block grammar, inline markup like *emphasis* isn't parsed
+            ("markdown", Language::new(tree_sitter_md::LANGUAGE), tree_sitter_md::HIGHLIGHT_QUERY_BLOCK, &["md", "markdown"]),
+        ];
+        for (name, language, query, extensions) in builtin {
+            match Grammar::new(name, language, query, None) {
+                Ok(grammar) => {
+                    out.grammars.insert(name.to_string(), Rc::new(grammar));
+                }
+                Err(e) => errors.push(format!("built in {name} grammar: {e}")),
+            }
+            for ext in extensions {
+                out.extensions.insert(ext.to_string(), name.to_string());
+            }
+        }
+        //there's no lua grammar to build in, one gets loaded from grammars/lua.so in the config dir
+        out.extensions.insert("lua".to_string(), "lua".to_string());
+        (out, errors)
+    }
+    pub fn detect(&self, path: &Path) -> Option<String> {
+        let ext = path.extension()?.to_str()?;
+        self.extensions.get(ext).cloned()
+    }
+    //the grammar for a filetype, loading grammars/<name>.so and grammars/<name>.scm the first time one is missing,
+    //a filetype without a grammar is fine but one that's there and won't load is an error
+    pub fn get(&mut self, name: &str) -> Result<Option<Rc<Grammar>>, String> {
+        if let Some(grammar) = self.grammars.get(name) {
+            return Ok(Some(grammar.clone()));
+        }
+        let dir = crate::config_dir().join("grammars");
+        let library = dir.join(format!("{name}.so"));
+        if !library.exists() {
+            return Ok(None);
+        }
+        let query = match std::fs::read_to_string(dir.join(format!("{name}.scm"))) {
+            Ok(query) => query,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("couldn't read the highlight query for {name}: {e}")),
+        };
+        self.register(name, Some((&library, &format!("tree_sitter_{name}"))), Some(query), &[])?;
+        Ok(self.grammars.get(name).cloned())
+    }
+    //adds or changes a grammar, without a library the one already registered under `name` keeps its language
+    pub fn register(&mut self, name: &str, library: Option<(&Path, &str)>, query: Option<String>, extensions: &[String]) -> Result<(), String> {
+        let old = self.grammars.get(name);
+        let (language, library) = match library {
+            Some((path, symbol)) => {
+                let (language, library) = load(path, symbol)?;
+                (language, Some(library))
+            }
+            None => {
+                let old = old.ok_or(format!("no grammar for {name}, give a library to load it from"))?;
+                (old.language.clone(), old.library.clone())
+            }
+        };
+        let query = query.or(old.map(|g| g.source.clone())).unwrap_or_default();
+        let grammar = Grammar::new(name, language, &query, library)?;
+        self.grammars.insert(name.to_string(), Rc::new(grammar));
+        for ext in extensions {
+            self.extensions.insert(ext.clone(), name.to_string());
+        }
+        Ok(())
+    }
+}
+
+fn load(path: &Path, symbol: &str) -> Result<(Language, Rc<Library>), String> {
+    let err = |e: libloading::Error| format!("couldn't load grammar {}: {e}", path.display());
+    unsafe {
+        let library = Library::new(path).map_err(err)?;
+        let func = *library.get::<unsafe extern "C" fn() -> *const ()>(symbol.as_bytes()).map_err(err)?;
+        let language = Language::new(tree_sitter_language::LanguageFn::from_raw(func));
+        Ok((language, Rc::new(library)))
+    }
+}
+
+pub fn point(rope: &Rope, byte: usize) -> Point {
+    let row = rope.byte_to_line(byte);
+    Point { row, column: byte - rope.line_to_byte(row) }
+}
+
+//a buffer's parse tree, edits mark it stale and it's parsed again before it gets drawn
+pub struct Syntax {
+    parser: Parser,
+    tree: Option<Tree>,
+    stale: bool,
+    //dropped last, it may hold the library the parser and tree come from
+    pub grammar: Rc<Grammar>,
+}
+
+impl fmt::Debug for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Syntax").field("grammar", &self.grammar.name).finish()
+    }
+}
+
+impl Syntax {
+    pub fn new(grammar: Rc<Grammar>) -> Option<Self> {
+        let mut parser = Parser::new();
+        parser.set_language(&grammar.language).ok()?;
+        Some(Self { parser, tree: None, stale: true, grammar })
+    }
+    pub fn edit(&mut self, edit: &InputEdit) {
+        if let Some(tree) = &mut self.tree {
+            tree.edit(edit);
+        }
+        self.stale = true;
+    }
+    pub fn update(&mut self, rope: &Rope) {
+        if !self.stale {
+            return;
+        }
+        let len = rope.len_bytes();
+        self.tree = self.parser.parse_with_options(&mut |byte, _| {
+            if byte >= len {
+                return &[][..];
+            }
+            let (chunk, start, _, _) = rope.chunk_at_byte(byte);
+            &chunk.as_bytes()[byte - start..]
+        }, self.tree.as_ref(), None);
+        self.stale = false;
+    }
+    //the styled char ranges touching `range`, where captures overlap the earlier pattern in the query wins
+    pub fn highlights(&self, rope: &Rope, range: Range<usize>, theme: &Theme) -> Vec<(Range<usize>, Style)> {
+        let Some(tree) = &self.tree else {
+            return Vec::new();
+        };
+        let len = rope.len_bytes();
+        let query = &self.grammar.query;
+        let names = query.capture_names();
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(rope.char_to_byte(range.start)..rope.char_to_byte(range.end));
+        let text = |node: Node| {
+            let r = node.byte_range();
+            rope.byte_slice(r.start.min(len)..r.end.min(len)).chunks().map(str::as_bytes)
+        };
+        let mut out = Vec::new();
+        let mut matches = cursor.matches(query, tree.root_node(), text);
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let Some(style) = theme.capture(names[capture.index as usize]) else {
+                    continue;
+                };
+                let r = capture.node.byte_range();
+                let r = rope.byte_to_char(r.start.min(len))..rope.byte_to_char(r.end.min(len));
+                out.push((m.pattern_index, r, style));
+            }
+        }
+        out.sort_by_key(|(pattern, ..)| *pattern);
+        out.into_iter().map(|(_, r, style)| (r, style)).collect()
+    }
+}
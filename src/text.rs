@@ -0,0 +1,529 @@
+//text storage, the characters live in a rope and styling lives in a separate span layer
+use std::{ops::Range, rc::Rc};
+
+use mlua::{FromLua, IntoLua, Lua, Value};
+use regex::Regex;
+use ropey::{Rope, RopeSlice};
+use sdl2::{pixels::Color, ttf::FontStyle};
+use tree_sitter::InputEdit;
+
+use crate::{syntax::{self, Grammar, Syntax}, theme::Theme};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub font_style: FontStyle,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fg: None,
+            bg: None,
+            font_style: FontStyle::NORMAL,
+        }
+    }
+}
+
+impl Style {
+    //lays `other` over this style, fields `other` leaves unset are kept
+    pub fn merge(&mut self, other: &Style) {
+        if other.fg.is_some() {
+            self.fg = other.fg;
+        }
+        if other.bg.is_some() {
+            self.bg = other.bg;
+        }
+        self.font_style |= other.font_style;
+    }
+}
+
+impl FromLua for Style {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+        let Value::Table(t) = value else {
+            return Err(mlua::Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Style".to_string(),
+                message: Some("style must be a table".to_string()),
+            });
+        };
+        let mut font_style = FontStyle::NORMAL;
+        if t.get::<Option<bool>>("bold")?.unwrap_or(false) {
+            font_style |= FontStyle::BOLD;
+        }
+        if t.get::<Option<bool>>("italic")?.unwrap_or(false) {
+            font_style |= FontStyle::ITALIC;
+        }
+        if t.get::<Option<bool>>("underline")?.unwrap_or(false) {
+            font_style |= FontStyle::UNDERLINE;
+        }
+        Ok(Self {
+            fg: t.get::<Option<u32>>("fg")?.map(crate::rgba),
+            bg: t.get::<Option<u32>>("bg")?.map(crate::rgba),
+            font_style,
+        })
+    }
+}
+
+impl IntoLua for Style {
+    fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+        let t = lua.create_table()?;
+        t.set("fg", self.fg.map(crate::from_rgba))?;
+        t.set("bg", self.bg.map(crate::from_rgba))?;
+        t.set("bold", self.font_style.contains(FontStyle::BOLD))?;
+        t.set("italic", self.font_style.contains(FontStyle::ITALIC))?;
+        t.set("underline", self.font_style.contains(FontStyle::UNDERLINE))?;
+        Ok(Value::Table(t))
+    }
+}
+
+//a style applied to the chars in `start..end`, indices are char indices into the rope
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub style: Style,
+}
+
+//a mark in the sign column, it moves with its line as text is added and removed above it
+#[derive(Clone, Debug)]
+pub struct Sign {
+    pub line: usize,
+    pub text: String,
+    //the highlight group it's drawn with
+    pub hl: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RegionKind {
+    Char,
+    Line,
+    Block,
+}
+
+impl RegionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Char => "char",
+            Self::Line => "line",
+            Self::Block => "block",
+        }
+    }
+}
+
+//an inclusive stretch of text between two (x, y) positions, like a vim visual selection
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Region {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub kind: RegionKind,
+}
+
+impl Region {
+    //puts start before end, for blocks the columns are sorted separately
+    pub fn normalized(&self) -> Self {
+        let (mut start, mut end) = (self.start, self.end);
+        if (end.1, end.0) < (start.1, start.0) {
+            std::mem::swap(&mut start, &mut end);
+        }
+        if self.kind == RegionKind::Block && end.0 < start.0 {
+            std::mem::swap(&mut start.0, &mut end.0);
+        }
+        Self { start, end, kind: self.kind }
+    }
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        let r = self.normalized();
+        if y < r.start.1 || y > r.end.1 {
+            return false;
+        }
+        match r.kind {
+            RegionKind::Char => (y, x) >= (r.start.1, r.start.0) && (y, x) <= (r.end.1, r.end.0),
+            RegionKind::Line => true,
+            RegionKind::Block => x >= r.start.0 && x <= r.end.0,
+        }
+    }
+}
+
+impl FromLua for Region {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+        let Value::Table(t) = value else {
+            return Err(mlua::Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Region".to_string(),
+                message: Some("region must be a table of x1, y1, x2, y2 and kind".to_string()),
+            });
+        };
+        let kind = match t.get::<Option<String>>("kind")?.as_deref() {
+            None | Some("char") => RegionKind::Char,
+            Some("line") => RegionKind::Line,
+            Some("block") => RegionKind::Block,
+            Some(other) => return Err(mlua::Error::runtime(format!("unknown region kind {other}"))),
+        };
+        Ok(Self {
+            start: (t.get("x1")?, t.get("y1")?),
+            end: (t.get("x2")?, t.get("y2")?),
+            kind,
+        })
+    }
+}
+
+impl IntoLua for Region {
+    fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+        let t = lua.create_table()?;
+        t.set("x1", self.start.0)?;
+        t.set("y1", self.start.1)?;
+        t.set("x2", self.end.0)?;
+        t.set("y2", self.end.1)?;
+        t.set("kind", self.kind.name())?;
+        Ok(Value::Table(t))
+    }
+}
+
+//how many chars of text a search looks at in one go, rounded up to the end of a line
+const SEARCH_BLOCK: usize = 64 * 1024;
+
+//a change to the text, kept so it can be undone
+#[derive(Clone, Debug)]
+pub enum Edit {
+    Insert(usize, String),
+    Remove(usize, String),
+}
+
+#[derive(Debug)]
+pub struct Text {
+    rope: Rope,
+    spans: Vec<Span>,
+    //edits made since the last take_journal
+    journal: Vec<Edit>,
+    //the parse tree for highlighting, kept in step with every edit
+    syntax: Option<Syntax>,
+    signs: Vec<Sign>,
+    //goes up with every change, so anything can tell whether the text changed since it last looked
+    tick: usize,
+}
+
+impl Text {
+    pub fn new() -> Self {
+        Self {
+            rope: Rope::new(),
+            spans: Vec::new(),
+            journal: Vec::new(),
+            syntax: None,
+            signs: Vec::new(),
+            tick: 0,
+        }
+    }
+    pub fn from_str(text: &str) -> Self {
+        Self {
+            rope: Rope::from_str(text),
+            spans: Vec::new(),
+            journal: Vec::new(),
+            syntax: None,
+            signs: Vec::new(),
+            tick: 0,
+        }
+    }
+    pub fn rope(&self) -> &Rope {
+        &self.rope
+    }
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+    pub fn len_lines(&self) -> usize {
+        self.rope.len_lines()
+    }
+    //the line without its line break
+    pub fn line(&self, y: usize) -> RopeSlice<'_> {
+        let line = self.rope.line(y);
+        let mut len = line.len_chars();
+        if len > 0 && line.char(len - 1) == '\n' {
+            len -= 1;
+        }
+        line.slice(..len)
+    }
+    pub fn line_len(&self, y: usize) -> usize {
+        self.line(y).len_chars()
+    }
+    pub fn line_to_char(&self, y: usize) -> usize {
+        self.rope.line_to_char(y)
+    }
+    //the nearest position that's in the text, x can be the end of its line
+    pub fn clamp_pos(&self, x: usize, y: usize) -> (usize, usize) {
+        let y = y.min(self.len_lines() - 1);
+        (x.min(self.line_len(y)), y)
+    }
+    pub fn pos_to_char(&self, x: usize, y: usize) -> usize {
+        self.rope.line_to_char(y) + x.min(self.line_len(y))
+    }
+    //the char ranges a region covers, blocks get one range per line
+    pub fn region_ranges(&self, region: &Region) -> Vec<Range<usize>> {
+        let r = region.normalized();
+        let (y1, y2) = (r.start.1.min(self.len_lines() - 1), r.end.1.min(self.len_lines() - 1));
+        match r.kind {
+            RegionKind::Char => {
+                let start = self.pos_to_char(r.start.0, y1);
+                let end = (self.pos_to_char(r.end.0, y2) + 1).min(self.len_chars()).max(start);
+                let range = start..end;
+                vec![range]
+            }
+            RegionKind::Line => {
+                let start = self.line_to_char(y1);
+                let end = if y2 + 1 < self.len_lines() {
+                    self.line_to_char(y2 + 1)
+                } else {
+                    self.len_chars()
+                };
+                let range = start..end;
+                vec![range]
+            }
+            RegionKind::Block => (y1..=y2).map(|y| {
+                let start = self.pos_to_char(r.start.0, y);
+                let end = (self.pos_to_char(r.end.0, y) + 1).min(self.line_to_char(y) + self.line_len(y));
+                start..end.max(start)
+            }).collect(),
+        }
+    }
+    //the text in a region, lines and blocks get a line break after every line
+    pub fn region_text(&self, region: &Region) -> String {
+        let mut out = String::new();
+        for range in self.region_ranges(region) {
+            out.push_str(&self.rope.slice(range).to_string());
+            if region.kind != RegionKind::Char && !out.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+        out
+    }
+    //`range` cut into blocks of whole lines, searching goes a block at a time so a big file isn't copied into one string
+    fn search_blocks(&self, range: Range<usize>) -> Vec<Range<usize>> {
+        let mut out = Vec::new();
+        let mut start = range.start;
+        while start < range.end {
+            let target = (start + SEARCH_BLOCK).min(range.end);
+            let line = self.rope.char_to_line(target);
+            let end = if line + 1 < self.len_lines() { self.rope.line_to_char(line + 1) } else { self.len_chars() };
+            let end = end.min(range.end).max(target);
+            out.push(start..end);
+            start = end;
+        }
+        out
+    }
+    fn find_in_block(&self, re: &Regex, block: Range<usize>) -> Vec<Range<usize>> {
+        let slice = self.rope.slice(block.clone());
+        let text = slice.to_string();
+        re.find_iter(&text).map(|m| {
+            block.start + slice.byte_to_char(m.start())..block.start + slice.byte_to_char(m.end())
+        }).collect()
+    }
+    //char ranges of the matches of `re` in `range`, a match can't run over from one block into the next
+    pub fn find(&self, re: &Regex, range: Range<usize>) -> Vec<Range<usize>> {
+        self.search_blocks(range).into_iter().flat_map(|block| self.find_in_block(re, block)).collect()
+    }
+    //the first match starting after `from`, or the last one before it going backwards, wrapping around the ends,
+    //only as much of the text as it takes to find one is searched
+    pub fn find_next(&self, re: &Regex, from: usize, backward: bool) -> Option<Range<usize>> {
+        let y = self.rope.char_to_line(from);
+        //the line `from` is on is searched whole both times, so ^ and $ see all of it
+        let line_start = self.line_to_char(y);
+        let line_end = if y + 1 < self.len_lines() { self.line_to_char(y + 1) } else { self.len_chars() };
+        if backward {
+            let before = self.search_blocks(0..line_end).into_iter().rev().find_map(|block| {
+                self.find_in_block(re, block).into_iter().rfind(|m| m.start < from)
+            });
+            before.or_else(|| self.search_blocks(line_start..self.len_chars()).into_iter().rev().find_map(|block| self.find_in_block(re, block).pop()))
+        } else {
+            let after = self.search_blocks(line_start..self.len_chars()).into_iter().find_map(|block| {
+                self.find_in_block(re, block).into_iter().find(|m| m.start > from)
+            });
+            after.or_else(|| self.search_blocks(0..line_end).into_iter().find_map(|block| self.find_in_block(re, block).into_iter().next()))
+        }
+    }
+    pub fn char_to_pos(&self, idx: usize) -> (usize, usize) {
+        let y = self.rope.char_to_line(idx);
+        (idx - self.rope.line_to_char(y), y)
+    }
+    pub fn insert(&mut self, idx: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        //typing a run of chars becomes one edit
+        match self.journal.last_mut() {
+            Some(Edit::Insert(at, s)) if *at + s.chars().count() == idx => s.push_str(text),
+            _ => self.journal.push(Edit::Insert(idx, text.to_string())),
+        }
+        self.raw_insert(idx, text);
+    }
+    pub fn remove(&mut self, range: Range<usize>) {
+        if range.start >= range.end {
+            return;
+        }
+        let text = self.rope.slice(range.clone()).to_string();
+        //and so does a run of backspaces or deletes
+        match self.journal.last_mut() {
+            Some(Edit::Remove(at, s)) if range.end == *at => {
+                s.insert_str(0, &text);
+                *at = range.start;
+            }
+            Some(Edit::Remove(at, s)) if range.start == *at => s.push_str(&text),
+            _ => self.journal.push(Edit::Remove(range.start, text)),
+        }
+        self.raw_remove(range);
+    }
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+    pub fn has_journal(&self) -> bool {
+        !self.journal.is_empty()
+    }
+    pub fn take_journal(&mut self) -> Vec<Edit> {
+        std::mem::take(&mut self.journal)
+    }
+    //these replay an edit without journaling it, they return where the edit happened
+    pub fn apply(&mut self, edit: &Edit) -> usize {
+        match edit {
+            Edit::Insert(at, s) => self.raw_insert(*at, s),
+            Edit::Remove(at, s) => self.raw_remove(*at..*at + s.chars().count()),
+        }
+        match edit {
+            Edit::Insert(at, _) | Edit::Remove(at, _) => *at,
+        }
+    }
+    pub fn revert(&mut self, edit: &Edit) -> usize {
+        match edit {
+            Edit::Insert(at, s) => self.raw_remove(*at..*at + s.chars().count()),
+            Edit::Remove(at, s) => self.raw_insert(*at, s),
+        }
+        match edit {
+            Edit::Insert(at, _) | Edit::Remove(at, _) => *at,
+        }
+    }
+    fn raw_insert(&mut self, idx: usize, text: &str) {
+        self.tick += 1;
+        let n = text.chars().count();
+        let lines = text.matches('\n').count();
+        if lines > 0 {
+            //new lines put in front of a line push its sign down with it
+            let line = self.rope.char_to_line(idx);
+            let whole = idx == self.rope.line_to_char(line) && text.ends_with('\n');
+            for sign in self.signs.iter_mut() {
+                if sign.line > line || (whole && sign.line == line) {
+                    sign.line += lines;
+                }
+            }
+        }
+        let start = self.rope.char_to_byte(idx);
+        let start_position = syntax::point(&self.rope, start);
+        self.rope.insert(idx, text);
+        if let Some(syntax) = &mut self.syntax {
+            let end = start + text.len();
+            syntax.edit(&InputEdit {
+                start_byte: start,
+                old_end_byte: start,
+                new_end_byte: end,
+                start_position,
+                old_end_position: start_position,
+                new_end_position: syntax::point(&self.rope, end),
+            });
+        }
+        for span in self.spans.iter_mut() {
+            if span.start >= idx {
+                span.start += n;
+            }
+            if span.end > idx {
+                span.end += n;
+            }
+        }
+    }
+    fn raw_remove(&mut self, range: Range<usize>) {
+        self.tick += 1;
+        let n = range.end - range.start;
+        let map = |p: usize| {
+            if p <= range.start {
+                p
+            } else if p >= range.end {
+                p - n
+            } else {
+                range.start
+            }
+        };
+        let (first, last) = (self.rope.char_to_line(range.start), self.rope.char_to_line(range.end));
+        if last > first {
+            //signs on lines that go away go with them
+            let whole = range.start == self.rope.line_to_char(first) && range.end == self.rope.line_to_char(last);
+            let gone = if whole { first..last } else { first + 1..last + 1 };
+            self.signs.retain(|s| !gone.contains(&s.line));
+            for sign in self.signs.iter_mut() {
+                if sign.line >= gone.end {
+                    sign.line -= last - first;
+                }
+            }
+        }
+        let (start, end) = (self.rope.char_to_byte(range.start), self.rope.char_to_byte(range.end));
+        let start_position = syntax::point(&self.rope, start);
+        let old_end_position = syntax::point(&self.rope, end);
+        self.rope.remove(range.clone());
+        if let Some(syntax) = &mut self.syntax {
+            syntax.edit(&InputEdit {
+                start_byte: start,
+                old_end_byte: end,
+                new_end_byte: start,
+                start_position,
+                old_end_position,
+                new_end_position: start_position,
+            });
+        }
+        for span in self.spans.iter_mut() {
+            span.start = map(span.start);
+            span.end = map(span.end);
+        }
+        self.spans.retain(|s| s.start < s.end);
+    }
+    pub fn add_span(&mut self, range: Range<usize>, style: Style) {
+        if range.start < range.end {
+            self.spans.push(Span {
+                start: range.start,
+                end: range.end,
+                style,
+            });
+        }
+    }
+    //parses the text with `grammar`, or stops highlighting it
+    pub fn set_syntax(&mut self, grammar: Option<Rc<Grammar>>) {
+        self.syntax = grammar.and_then(Syntax::new);
+    }
+    pub fn update_syntax(&mut self) {
+        if let Some(syntax) = &mut self.syntax {
+            syntax.update(&self.rope);
+        }
+    }
+    pub fn highlights(&self, range: Range<usize>, theme: &Theme) -> Vec<(Range<usize>, Style)> {
+        match &self.syntax {
+            Some(syntax) => syntax.highlights(&self.rope, range, theme),
+            None => Vec::new(),
+        }
+    }
+    //a line has one sign at most, setting another replaces it and no text takes it away
+    pub fn set_sign(&mut self, line: usize, sign: Option<(String, String)>) {
+        self.signs.retain(|s| s.line != line);
+        if let Some((text, hl)) = sign {
+            self.signs.push(Sign { line, text, hl });
+        }
+    }
+    pub fn sign(&self, line: usize) -> Option<&Sign> {
+        self.signs.iter().find(|s| s.line == line)
+    }
+    pub fn has_signs(&self) -> bool {
+        !self.signs.is_empty()
+    }
+    pub fn clear_signs(&mut self) {
+        self.signs.clear();
+    }
+    pub fn clear_spans(&mut self) {
+        self.spans.clear();
+    }
+    //the spans touching `range`, in the order they were added
+    pub fn spans_in(&self, range: Range<usize>) -> impl Iterator<Item = &Span> {
+        self.spans.iter().filter(move |s| s.start < range.end && s.end > range.start)
+    }
+}
@@ -0,0 +1,366 @@
+//named highlight groups, the renderer asks for them by name and tree-sitter captures fall back to them
+//themes are lua files, base16 yaml or vs code json in config_dir()/themes
+use std::{collections::HashMap, path::Path};
+
+use sdl2::{pixels::Color, ttf::FontStyle};
+use serde_json::Value as Json;
+
+use crate::{rgba, text::Style};
+
+pub struct Theme {
+    groups: HashMap<String, Style>,
+    //a linked group draws with the style of the one it points at
+    links: HashMap<String, String>,
+}
+
+impl Theme {
+    pub fn new() -> Self {
+        let mut out = Self {
+            groups: HashMap::new(),
+            links: HashMap::new(),
+        };
+        let fg = |c: u32| Style { fg: Some(rgba(c)), ..Style::default() };
+        let bg = |c: u32| Style { bg: Some(rgba(c)), ..Style::default() };
+        let with = |c: u32, font_style: FontStyle| Style { fg: Some(rgba(c)), bg: None, font_style };
+        let groups = [
+            ("Normal", Style { fg: Some(rgba(0xffffffff)), bg: Some(rgba(0x181818ff)), font_style: FontStyle::NORMAL }),
+            //unset colors draw the cursor in the color of the text under it
+            ("Cursor", Style::default()),
+            ("Visual", bg(0x3e4451ff)),
+            ("Search", bg(0x5c4a1fff)),
+            ("Divider", bg(0x3a3a3aff)),
+            ("LineNr", fg(0x5c6370ff)),
+            ("CursorLineNr", fg(0xdcdfe4ff)),
+            ("SignColumn", fg(0x5c6370ff)),
+            ("NonText", fg(0x5c6370ff)),
+            ("StatusLine", Style { fg: Some(rgba(0xdcdfe4ff)), bg: Some(rgba(0x2c313aff)), font_style: FontStyle::NORMAL }),
+            ("StatusLineNC", Style { fg: Some(rgba(0x7f848eff)), bg: Some(rgba(0x21252bff)), font_style: FontStyle::NORMAL }),
+            ("Comment", with(0x7f848eff, FontStyle::ITALIC)),
+            ("Keyword", fg(0xc678ddff)),
+            ("String", fg(0x98c379ff)),
+            ("Function", fg(0x61afefff)),
+            ("Type", fg(0xe5c07bff)),
+            ("Constant", fg(0xd19a66ff)),
+            ("Property", fg(0xe06c75ff)),
+            ("Operator", fg(0x56b6c2ff)),
+            ("Special", fg(0x56b6c2ff)),
+            ("Title", with(0xe06c75ff, FontStyle::BOLD)),
+            ("Underlined", with(0x61afefff, FontStyle::UNDERLINE)),
+            ("Error", fg(0xe06c75ff)),
+        ];
+        for (name, style) in groups {
+            out.groups.insert(name.to_string(), style);
+        }
+        let links = [
+            ("Number", "Constant"),
+            ("Boolean", "Constant"),
+            ("Constructor", "Type"),
+            ("Attribute", "Type"),
+            ("Label", "Special"),
+            ("@escape", "Special"),
+            ("@string.escape", "Special"),
+            ("@variable.builtin", "Property"),
+            ("@punctuation.special", "Property"),
+            ("@text.title", "Title"),
+            ("@text.literal", "String"),
+            ("@text.uri", "Underlined"),
+            ("@text.reference", "Keyword"),
+            ("TabLine", "StatusLineNC"),
+            ("TabLineSel", "StatusLine"),
+            ("TabLineFill", "StatusLineNC"),
+        ];
+        for (name, to) in links {
+            out.links.insert(name.to_string(), to.to_string());
+        }
+        out
+    }
+    fn lookup<'a>(&'a self, mut name: &'a str) -> Option<Style> {
+        //links can go through a few groups, but not round in circles
+        for _ in 0..16 {
+            match self.links.get(name) {
+                Some(to) => name = to,
+                None => return self.groups.get(name).copied(),
+            }
+        }
+        None
+    }
+    pub fn get(&self, name: &str) -> Style {
+        self.lookup(name).unwrap_or_default()
+    }
+    pub fn fg(&self, name: &str) -> Color {
+        self.get(name).fg.or(self.get("Normal").fg).unwrap_or(Color::WHITE)
+    }
+    pub fn bg(&self, name: &str) -> Color {
+        self.get(name).bg.or(self.get("Normal").bg).unwrap_or(Color::BLACK)
+    }
+    pub fn set(&mut self, name: &str, style: Style) {
+        self.links.remove(name);
+        self.groups.insert(name.to_string(), style);
+    }
+    pub fn link(&mut self, name: &str, to: &str) {
+        self.groups.remove(name);
+        self.links.insert(name.to_string(), to.to_string());
+    }
+    //changes one color of a group and leaves the rest of it
+    pub fn set_fg(&mut self, name: &str, color: Color) {
+        let mut style = self.get(name);
+        style.fg = Some(color);
+        self.set(name, style);
+    }
+    pub fn set_bg(&mut self, name: &str, color: Color) {
+        let mut style = self.get(name);
+        style.bg = Some(color);
+        self.set(name, style);
+    }
+    //"@function.macro" tries @function.macro, then @function, then Function
+    pub fn capture(&self, capture: &str) -> Option<Style> {
+        let mut name = capture;
+        loop {
+            if let Some(style) = self.lookup(&format!("@{name}")) {
+                return Some(style);
+            }
+            match name.rfind('.') {
+                Some(i) => name = &name[..i],
+                None => break,
+            }
+        }
+        let mut chars = name.chars();
+        let group: String = chars.next().into_iter().flat_map(char::to_uppercase).chain(chars).collect();
+        self.lookup(&group)
+    }
+    //base16 yaml and vs code json, lua themes are run by config:colorscheme instead
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {e}", path.display()))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => self.base16(&text),
+            Some("json") => self.vscode(&text),
+            _ => Err(format!("unknown theme format {}", path.display())),
+        }
+    }
+    fn base16(&mut self, text: &str) -> Result<(), String> {
+        let mut base = [Color::BLACK; 16];
+        let mut found = 0;
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let Some(n) = key.trim().strip_prefix("base").and_then(|n| u8::from_str_radix(n, 16).ok()) else {
+                continue;
+            };
+            let value = value.split(" #").next().unwrap_or("").trim().trim_matches(['"', '\'']);
+            let color = parse_hex(value).ok_or(format!("bad color for {}: {value}", key.trim()))?;
+            if let Some(slot) = base.get_mut(n as usize) {
+                *slot = color;
+                found |= 1 << n;
+            }
+        }
+        if found != 0xffff {
+            return Err("a base16 theme needs base00 to base0F".to_string());
+        }
+        let style = |fg: Option<usize>, bg: Option<usize>, font_style| Style {
+            fg: fg.map(|n| base[n]),
+            bg: bg.map(|n| base[n]),
+            font_style,
+        };
+        let normal = FontStyle::NORMAL;
+        let groups = [
+            ("Normal", style(Some(0x5), Some(0x0), normal)),
+            ("Cursor", style(Some(0x0), Some(0x5), normal)),
+            ("Visual", style(None, Some(0x2), normal)),
+            ("Search", style(Some(0x1), Some(0xA), normal)),
+            ("Divider", style(None, Some(0x1), normal)),
+            ("LineNr", style(Some(0x3), None, normal)),
+            ("CursorLineNr", style(Some(0x4), None, normal)),
+            ("SignColumn", style(Some(0x3), None, normal)),
+            ("NonText", style(Some(0x3), None, normal)),
+            ("StatusLine", style(Some(0x4), Some(0x2), normal)),
+            ("StatusLineNC", style(Some(0x3), Some(0x1), normal)),
+            ("Comment", style(Some(0x3), None, FontStyle::ITALIC)),
+            ("Keyword", style(Some(0xE), None, normal)),
+            ("String", style(Some(0xB), None, normal)),
+            ("Function", style(Some(0xD), None, normal)),
+            ("Type", style(Some(0xA), None, normal)),
+            ("Constant", style(Some(0x9), None, normal)),
+            ("Property", style(Some(0x8), None, normal)),
+            ("Operator", style(Some(0x5), None, normal)),
+            ("Special", style(Some(0xC), None, normal)),
+            ("Title", style(Some(0xD), None, FontStyle::BOLD)),
+            ("Underlined", style(Some(0x8), None, FontStyle::UNDERLINE)),
+            ("Error", style(Some(0x8), None, normal)),
+        ];
+        for (name, style) in groups {
+            self.set(name, style);
+        }
+        Ok(())
+    }
+    fn vscode(&mut self, text: &str) -> Result<(), String> {
+        let json: Json = serde_json::from_str(&strip_jsonc(text)).map_err(|e| format!("bad theme json: {e}"))?;
+        let colors = &json["colors"];
+        let color = |key: &str, under: Option<Color>| colors[key].as_str().and_then(|c| parse_color(c, under));
+        let normal_bg = color("editor.background", None);
+        let (fg, bg) = (|key| color(key, None), |key| color(key, normal_bg));
+        //editor colors, the first key found for each slot is used
+        let slots: [(&str, bool, &[&str]); 20] = [
+            ("Normal", false, &["editor.foreground", "foreground"]),
+            ("Normal", true, &["editor.background"]),
+            ("Cursor", true, &["editorCursor.foreground"]),
+            ("Visual", true, &["editor.selectionBackground"]),
+            ("Search", true, &["editor.findMatchHighlightBackground", "editor.findMatchBackground"]),
+            ("Divider", true, &["editorGroup.border", "panel.border", "editorWidget.border"]),
+            ("LineNr", false, &["editorLineNumber.foreground"]),
+            ("CursorLineNr", false, &["editorLineNumber.activeForeground"]),
+            ("SignColumn", true, &["editorGutter.background"]),
+            ("Error", false, &["errorForeground", "editorError.foreground"]),
+            ("NonText", false, &["editorWhitespace.foreground"]),
+            ("StatusLine", false, &["statusBar.foreground"]),
+            ("StatusLine", true, &["statusBar.background"]),
+            ("StatusLineNC", false, &["statusBar.foreground"]),
+            ("StatusLineNC", true, &["statusBar.noFolderBackground", "statusBar.background"]),
+            ("TabLine", false, &["tab.inactiveForeground"]),
+            ("TabLine", true, &["tab.inactiveBackground"]),
+            ("TabLineSel", false, &["tab.activeForeground"]),
+            ("TabLineSel", true, &["tab.activeBackground"]),
+            ("TabLineFill", true, &["editorGroupHeader.tabsBackground"]),
+        ];
+        for (group, is_bg, keys) in slots {
+            let found = keys.iter().find_map(|k| if is_bg { bg(k) } else { fg(k) });
+            match found {
+                Some(c) if is_bg => self.set_bg(group, c),
+                Some(c) => self.set_fg(group, c),
+                None => {}
+            }
+        }
+        //token colors go on the groups whose textmate scope they select, the most specific selector wins
+        let scopes = [
+            ("comment", "Comment"),
+            ("string", "String"),
+            ("constant.character.escape", "Special"),
+            ("constant.numeric", "Number"),
+            ("constant.language", "Boolean"),
+            ("constant", "Constant"),
+            ("keyword.operator", "Operator"),
+            ("keyword", "Keyword"),
+            ("storage", "Keyword"),
+            ("entity.name.function", "Function"),
+            ("support.function", "Function"),
+            ("entity.name.type", "Type"),
+            ("support.type", "Type"),
+            ("entity.other.attribute-name", "Attribute"),
+            ("variable.other.property", "Property"),
+            ("variable.language", "@variable.builtin"),
+            ("markup.heading", "Title"),
+            ("markup.underline.link", "Underlined"),
+        ];
+        let mut best: HashMap<&str, usize> = HashMap::new();
+        for token in json["tokenColors"].as_array().into_iter().flatten() {
+            let settings = &token["settings"];
+            let selectors: Vec<&str> = match &token["scope"] {
+                Json::String(s) => s.split(',').collect(),
+                Json::Array(a) => a.iter().filter_map(|s| s.as_str()).collect(),
+                _ => continue,
+            };
+            for selector in selectors {
+                //"source.rust comment" only looks at the last scope
+                let Some(selector) = selector.split_whitespace().last() else {
+                    continue;
+                };
+                let depth = selector.split('.').count();
+                for (scope, group) in scopes {
+                    let selects = scope == selector || scope.starts_with(&format!("{selector}."));
+                    if !selects || best.get(group).is_some_and(|&d| d > depth) {
+                        continue;
+                    }
+                    best.insert(group, depth);
+                    let mut style = self.get(group);
+                    if let Some(c) = settings["foreground"].as_str().and_then(|c| parse_color(c, normal_bg)) {
+                        style.fg = Some(c);
+                    }
+                    if let Some(c) = settings["background"].as_str().and_then(|c| parse_color(c, normal_bg)) {
+                        style.bg = Some(c);
+                    }
+                    if let Some(font) = settings["fontStyle"].as_str() {
+                        style.font_style = FontStyle::NORMAL;
+                        for word in font.split_whitespace() {
+                            style.font_style |= match word {
+                                "bold" => FontStyle::BOLD,
+                                "italic" => FontStyle::ITALIC,
+                                "underline" => FontStyle::UNDERLINE,
+                                _ => FontStyle::NORMAL,
+                            };
+                        }
+                    }
+                    self.set(group, style);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+//"rrggbb" or "rgb", with or without a #
+pub fn parse_hex(s: &str) -> Option<Color> {
+    parse_color(s, None)
+}
+
+//colors with alpha get mixed into `under`, since everything is drawn opaque
+fn parse_color(s: &str, under: Option<Color>) -> Option<Color> {
+    let s = s.trim().trim_start_matches('#');
+    let byte = |i: usize| u8::from_str_radix(s.get(i..i + 2)?, 16).ok();
+    let nibble = |i: usize| u8::from_str_radix(s.get(i..i + 1)?, 16).ok().map(|n| n * 17);
+    let (r, g, b, a) = match s.len() {
+        3 => (nibble(0)?, nibble(1)?, nibble(2)?, 255),
+        4 => (nibble(0)?, nibble(1)?, nibble(2)?, nibble(3)?),
+        6 => (byte(0)?, byte(2)?, byte(4)?, 255),
+        8 => (byte(0)?, byte(2)?, byte(4)?, byte(6)?),
+        _ => return None,
+    };
+    let under = under.unwrap_or(Color::BLACK);
+    let mix = |c: u8, u: u8| ((c as u32 * a as u32 + u as u32 * (255 - a as u32)) / 255) as u8;
+    Some(Color::RGB(mix(r, under.r), mix(g, under.g), mix(b, under.b)))
+}
+
+//vs code themes are json with comments and trailing commas
+fn strip_jsonc(text: &str) -> String {
+    //goes over the text outside of strings, `f` gets each char and what's left after it
+    fn outside_strings(text: &str, mut f: impl FnMut(char, &mut std::iter::Peekable<std::str::Chars>, &mut String)) -> String {
+        let mut out = String::new();
+        let mut chars = text.chars().peekable();
+        let mut in_string = false;
+        while let Some(c) = chars.next() {
+            if in_string {
+                out.push(c);
+                match c {
+                    '\\' => out.extend(chars.next()),
+                    '"' => in_string = false,
+                    _ => {}
+                }
+            } else if c == '"' {
+                in_string = true;
+                out.push(c);
+            } else {
+                f(c, &mut chars, &mut out);
+            }
+        }
+        out
+    }
+    let text = outside_strings(text, |c, chars, out| match (c, chars.peek()) {
+        ('/', Some('/')) => while chars.next_if(|&c| c != '\n').is_some() {},
+        ('/', Some('*')) => {
+            chars.next();
+            let mut prev = ' ';
+            for c in chars.by_ref() {
+                if prev == '*' && c == '/' {
+                    break;
+                }
+                prev = c;
+            }
+        }
+        _ => out.push(c),
+    });
+    outside_strings(&text, |c, chars, out| {
+        let closes = c == ',' && chars.clone().find(|c| !c.is_whitespace()).is_some_and(|c| c == ']' || c == '}');
+        if !closes {
+            out.push(c);
+        }
+    })
+}
@@ -0,0 +1,119 @@
+//undo history, every committed group of edits is a node and undoing then editing starts a new branch
+use std::time::{Duration, SystemTime};
+
+use crate::text::{Edit, Text};
+
+struct Node {
+    parent: usize,
+    //the child redo goes to, which is the branch that was visited last
+    last_child: Option<usize>,
+    edits: Vec<Edit>,
+    time: SystemTime,
+}
+
+pub struct UndoTree {
+    //node 0 is the state before any edits, nodes are numbered in the order they were made
+    nodes: Vec<Node>,
+    current: usize,
+    saved: Option<usize>,
+}
+
+impl UndoTree {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                parent: 0,
+                last_child: None,
+                edits: Vec::new(),
+                time: SystemTime::now(),
+            }],
+            current: 0,
+            saved: Some(0),
+        }
+    }
+    pub fn commit(&mut self, edits: Vec<Edit>) {
+        if edits.is_empty() {
+            return;
+        }
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            parent: self.current,
+            last_child: None,
+            edits,
+            time: SystemTime::now(),
+        });
+        self.nodes[self.current].last_child = Some(node);
+        self.current = node;
+    }
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.current);
+    }
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.current)
+    }
+    //these return the char index of the first edit they touched, so the cursor can go there
+    pub fn undo(&mut self, text: &mut Text) -> Option<usize> {
+        if self.current == 0 {
+            return None;
+        }
+        let node = &self.nodes[self.current];
+        let mut pos = None;
+        for edit in node.edits.iter().rev() {
+            pos = Some(text.revert(edit));
+        }
+        let parent = node.parent;
+        self.nodes[parent].last_child = Some(self.current);
+        self.current = parent;
+        pos
+    }
+    pub fn redo(&mut self, text: &mut Text) -> Option<usize> {
+        let child = self.nodes[self.current].last_child?;
+        let mut pos = None;
+        for edit in self.nodes[child].edits.iter() {
+            let at = text.apply(edit);
+            pos.get_or_insert(at);
+        }
+        self.current = child;
+        pos
+    }
+    //moves to state `n`, undoing back to where the branches meet and redoing down the other one
+    pub fn goto(&mut self, target: usize, text: &mut Text) -> Option<usize> {
+        let target = target.min(self.nodes.len() - 1);
+        let ancestors = |mut n: usize| {
+            let mut path = vec![n];
+            while n != 0 {
+                n = self.nodes[n].parent;
+                path.push(n);
+            }
+            path
+        };
+        let down = ancestors(target);
+        let mut pos = None;
+        while !down.contains(&self.current) {
+            pos = self.undo(text).or(pos);
+        }
+        let i = down.iter().position(|&n| n == self.current).unwrap();
+        for &n in down[..i].iter().rev() {
+            self.nodes[self.current].last_child = Some(n);
+            pos = self.redo(text).or(pos);
+        }
+        pos
+    }
+    pub fn earlier(&mut self, steps: usize, text: &mut Text) -> Option<usize> {
+        self.goto(self.current.saturating_sub(steps), text)
+    }
+    pub fn later(&mut self, steps: usize, text: &mut Text) -> Option<usize> {
+        self.goto(self.current + steps, text)
+    }
+    //goes to the newest state that existed `by` before the current one, like :earlier 5m
+    pub fn earlier_time(&mut self, by: Duration, text: &mut Text) -> Option<usize> {
+        let time = self.nodes[self.current].time.checked_sub(by);
+        let target = time.and_then(|t| self.nodes.iter().rposition(|n| n.time <= t)).unwrap_or(0);
+        self.goto(target, text)
+    }
+    pub fn later_time(&mut self, by: Duration, text: &mut Text) -> Option<usize> {
+        let time = self.nodes[self.current].time + by;
+        let target = self.nodes.iter().rposition(|n| n.time <= time).unwrap_or(0);
+        self.goto(target.max(self.current), text)
+    }
+}
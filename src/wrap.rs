@@ -0,0 +1,74 @@
+//soft wrap, cutting a buffer line into the screen rows it's drawn on
+use ropey::RopeSlice;
+
+use crate::{pane::char_cols, Config};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Row {
+    //the char the row starts at
+    pub start: usize,
+    //columns before the row's text, showbreak is drawn at the end of them
+    pub indent: usize,
+}
+
+//the rows `line` takes up `width` columns wide, with `end` the spot past the last char takes a column too so the cursor fits there
+pub fn rows(line: RopeSlice, width: usize, end: bool, config: &Config) -> Vec<Row> {
+    let mut out = vec![Row { start: 0, indent: 0 }];
+    let showbreak = config.showbreak.chars().count();
+    let lead = if config.breakindent {
+        line.chars().take_while(|c| matches!(c, ' ' | '\t')).map(|c| char_cols(c, config)).sum()
+    } else {
+        0
+    };
+    //a deep indent would leave too little room, so past half the width only showbreak is kept
+    let indent = if lead + showbreak <= width / 2 { lead + showbreak } else { showbreak.min(width / 2) };
+    let cols: Vec<usize> = line.chars().map(|c| char_cols(c, config)).chain(end.then_some(1)).collect();
+    let mut col = 0;
+    //where the row can be cut after a space when breaking at words
+    let mut space = None;
+    for (i, &w) in cols.iter().enumerate() {
+        let start = out.last().unwrap().start;
+        if col + w > width && i > start {
+            let at = match space {
+                Some(at) if config.linebreak && indent + cols[at..i].iter().sum::<usize>() + w <= width => at,
+                _ => i,
+            };
+            out.push(Row { start: at, indent });
+            col = indent + cols[at..i].iter().sum::<usize>();
+            space = None;
+        }
+        col += w;
+        if i < line.len_chars() && matches!(line.char(i), ' ' | '\t') {
+            space = Some(i + 1);
+        }
+    }
+    out
+}
+
+//the row char `x` is on and the screen column it starts at
+pub fn locate(rows: &[Row], line: RopeSlice, x: usize, config: &Config) -> (usize, usize) {
+    let row = rows.iter().rposition(|r| r.start <= x).unwrap_or(0);
+    let r = rows[row];
+    let col = line.chars_at(r.start.min(line.len_chars())).take(x.saturating_sub(r.start)).map(|c| char_cols(c, config)).sum::<usize>();
+    (row, r.indent + col)
+}
+
+//the char on `row` under screen column `col`, or the last one on it when the row is shorter
+pub fn char_at(rows: &[Row], line: RopeSlice, row: usize, col: usize, config: &Config) -> usize {
+    let row = row.min(rows.len() - 1);
+    let r = rows[row];
+    let len = line.len_chars();
+    let start = r.start.min(len);
+    //the last char of a row that isn't the last one belongs to it, the spot after it is the next row's
+    let end = rows.get(row + 1).map_or(len, |next| next.start.saturating_sub(1).max(start));
+    let mut c = r.indent;
+    let mut x = start;
+    for ch in line.chars_at(start).take(end - start) {
+        c += char_cols(ch, config);
+        if c > col {
+            break;
+        }
+        x += 1;
+    }
+    x
+}